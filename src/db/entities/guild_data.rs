@@ -9,6 +9,16 @@ pub struct Model {
     pub id: u64,
     pub personal_allowed: bool,
     pub manager_role: Option<u64>,
+    /// Whether to delete messages after posting their stickers, `None` uses the bot's default
+    pub delete_original: Option<bool>,
+    /// Sticker posts per minute for each user, `None` uses the bot's default
+    pub user_rate_limit: Option<u32>,
+    /// Sticker posts per minute in each channel, `None` uses the bot's default
+    pub channel_rate_limit: Option<u32>,
+    /// Sticker posts per minute in the whole guild, `None` uses the bot's default
+    pub guild_rate_limit: Option<u32>,
+    /// Whether to post stickers as the bot instead of through webhooks, `None` uses the bot's default
+    pub post_as_bot: Option<bool>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -9,6 +9,8 @@ pub struct Model {
     pub guild_id: u64,
     #[sea_orm(primary_key, auto_increment = false)]
     pub pack_id: u64,
+    /// Packs subscribed to earlier come first when resolving stickers
+    pub position: u64,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
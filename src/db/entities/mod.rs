@@ -2,6 +2,7 @@
 
 pub mod prelude;
 
+pub mod channel_webhook;
 pub mod guild_data;
 pub mod guild_pack_rel;
 pub mod role;
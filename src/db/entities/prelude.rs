@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.6
 
+pub use super::channel_webhook::Entity as ChannelWebhook;
 pub use super::guild_data::Entity as GuildData;
 pub use super::guild_pack_rel::Entity as GuildPackRel;
 pub use super::role::Entity as Role;
@@ -13,6 +13,28 @@ pub struct Model {
     pub guild: Option<u64>,
     pub user: Option<u64>,
     pub pack: Option<u64>,
+    pub image_source: ImageSource,
+    pub image_location: Option<String>,
+    pub content_type: Option<String>,
+    pub size: Option<u64>,
+    pub hash: Option<String>,
+    /// How often the sticker was posted
+    pub uses: u64,
+}
+
+/// Where the image data of a sticker is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum ImageSource {
+    /// A file in the server's local file system, `image_location` is the path
+    #[sea_orm(string_value = "local")]
+    Local,
+    /// An external image URL, `image_location` is the URL
+    #[sea_orm(string_value = "external")]
+    External,
+    /// A selfhosted image, `image_location` is the file name in the owner's sticker directory
+    #[sea_orm(string_value = "hosted")]
+    Hosted,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -9,7 +9,7 @@ pub struct Model {
     pub id: u64,
     pub prefix: String,
     pub display_name: Option<String>,
-    pub creator: Option<i32>,
+    pub creator: Option<u64>,
     pub creation_date: Option<String>,
 }
 
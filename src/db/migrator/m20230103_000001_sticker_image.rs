@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230103_000001_sticker_image"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // SQLite only supports one column per ALTER TABLE statement
+    manager.alter_table(
+      Table::alter()
+        .table(Sticker::Table)
+        .add_column(ColumnDef::new(Sticker::ImageSource).string().not_null().default("hosted"))
+        .to_owned()
+    ).await?;
+    manager.alter_table(
+      Table::alter()
+        .table(Sticker::Table)
+        .add_column(ColumnDef::new(Sticker::ImageLocation).string())
+        .to_owned()
+    ).await?;
+    manager.alter_table(
+      Table::alter()
+        .table(Sticker::Table)
+        .add_column(ColumnDef::new(Sticker::ContentType).string())
+        .to_owned()
+    ).await?;
+    manager.alter_table(
+      Table::alter()
+        .table(Sticker::Table)
+        .add_column(ColumnDef::new(Sticker::Size).big_unsigned())
+        .to_owned()
+    ).await?;
+    manager.alter_table(
+      Table::alter()
+        .table(Sticker::Table)
+        .add_column(ColumnDef::new(Sticker::Hash).string())
+        .to_owned()
+    ).await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for col in [
+      Sticker::Hash,
+      Sticker::Size,
+      Sticker::ContentType,
+      Sticker::ImageLocation,
+      Sticker::ImageSource,
+    ] {
+      manager.alter_table(Table::alter().table(Sticker::Table).drop_column(col).to_owned()).await?;
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Iden)]
+pub enum Sticker {
+  Table,
+  ImageSource,
+  ImageLocation,
+  ContentType,
+  Size,
+  Hash,
+}
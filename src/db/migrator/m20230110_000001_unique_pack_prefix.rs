@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230110_000001_unique_pack_prefix"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.create_index(
+      Index::create()
+        .name("idx-sticker_pack-prefix")
+        .table(StickerPack::Table)
+        .col(StickerPack::Prefix)
+        .unique()
+        .to_owned()
+    ).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.drop_index(
+      Index::drop().name("idx-sticker_pack-prefix").table(StickerPack::Table).to_owned()
+    ).await
+  }
+}
+
+#[derive(Iden)]
+pub enum StickerPack {
+  Table,
+  Prefix,
+}
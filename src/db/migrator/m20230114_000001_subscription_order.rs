@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230114_000001_subscription_order"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.alter_table(
+      Table::alter()
+        .table(GuildPackRel::Table)
+        .add_column(ColumnDef::new(GuildPackRel::Position).big_unsigned().not_null().default(0))
+        .to_owned()
+    ).await?;
+    manager.alter_table(
+      Table::alter()
+        .table(UserPackRel::Table)
+        .add_column(ColumnDef::new(UserPackRel::Position).big_unsigned().not_null().default(0))
+        .to_owned()
+    ).await?;
+
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.alter_table(
+      Table::alter().table(UserPackRel::Table).drop_column(UserPackRel::Position).to_owned()
+    ).await?;
+    manager.alter_table(
+      Table::alter().table(GuildPackRel::Table).drop_column(GuildPackRel::Position).to_owned()
+    ).await?;
+
+    Ok(())
+  }
+}
+
+#[derive(Iden)]
+pub enum GuildPackRel {
+  Table,
+  Position,
+}
+
+#[derive(Iden)]
+pub enum UserPackRel {
+  Table,
+  Position,
+}
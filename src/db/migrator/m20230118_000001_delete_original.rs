@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230118_000001_delete_original"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // NULL means the guild never decided, so the bot's configured default applies
+    manager.alter_table(
+      Table::alter()
+        .table(GuildData::Table)
+        .add_column(ColumnDef::new(GuildData::DeleteOriginal).boolean())
+        .to_owned()
+    ).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.alter_table(
+      Table::alter().table(GuildData::Table).drop_column(GuildData::DeleteOriginal).to_owned()
+    ).await
+  }
+}
+
+#[derive(Iden)]
+pub enum GuildData {
+  Table,
+  DeleteOriginal,
+}
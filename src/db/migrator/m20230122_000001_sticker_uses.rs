@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230122_000001_sticker_uses"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.alter_table(
+      Table::alter()
+        .table(Sticker::Table)
+        .add_column(ColumnDef::new(Sticker::Uses).big_unsigned().not_null().default(0))
+        .to_owned()
+    ).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.alter_table(
+      Table::alter().table(Sticker::Table).drop_column(Sticker::Uses).to_owned()
+    ).await
+  }
+}
+
+#[derive(Iden)]
+pub enum Sticker {
+  Table,
+  Uses,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230125_000001_rate_limits"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // NULL means the guild never decided, so the bot's configured default applies.
+    // SQLite can only add one column at a time.
+    for column in [GuildData::UserRateLimit, GuildData::ChannelRateLimit, GuildData::GuildRateLimit] {
+      manager.alter_table(
+        Table::alter()
+          .table(GuildData::Table)
+          .add_column(ColumnDef::new(column).unsigned())
+          .to_owned()
+      ).await?;
+    }
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for column in [GuildData::UserRateLimit, GuildData::ChannelRateLimit, GuildData::GuildRateLimit] {
+      manager.alter_table(
+        Table::alter().table(GuildData::Table).drop_column(column).to_owned()
+      ).await?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Iden)]
+pub enum GuildData {
+  Table,
+  UserRateLimit,
+  ChannelRateLimit,
+  GuildRateLimit,
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230128_000001_channel_webhook"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.create_table(
+      Table::create()
+        .table(ChannelWebhook::Table)
+        .col(ColumnDef::new(ChannelWebhook::Id).big_unsigned().not_null().primary_key())
+        .col(ColumnDef::new(ChannelWebhook::Channel).big_unsigned().not_null())
+        .col(ColumnDef::new(ChannelWebhook::Guild).big_unsigned().not_null())
+        .col(ColumnDef::new(ChannelWebhook::Token).string().not_null())
+        .col(ColumnDef::new(ChannelWebhook::CreatedAt).date_time().not_null())
+        .to_owned()
+    ).await?;
+    manager.create_index(
+      Index::create()
+        .name("idx-channel_webhook-channel")
+        .table(ChannelWebhook::Table)
+        .col(ChannelWebhook::Channel)
+        .to_owned()
+    ).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.drop_table(Table::drop().table(ChannelWebhook::Table).to_owned()).await
+  }
+}
+
+#[derive(Iden)]
+pub enum ChannelWebhook {
+  Table,
+  Id,
+  Channel,
+  Guild,
+  Token,
+  CreatedAt,
+}
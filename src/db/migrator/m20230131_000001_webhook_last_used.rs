@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+use serenity::async_trait;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+  fn name(&self) -> &str {
+    "m20230131_000001_webhook_last_used"
+  }
+}
+
+#[async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // NULL means the webhook hasn't posted anything since it was made
+    manager.alter_table(
+      Table::alter()
+        .table(ChannelWebhook::Table)
+        .add_column(ColumnDef::new(ChannelWebhook::LastUsed).date_time())
+        .to_owned()
+    ).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager.alter_table(
+      Table::alter().table(ChannelWebhook::Table).drop_column(ChannelWebhook::LastUsed).to_owned()
+    ).await
+  }
+}
+
+#[derive(Iden)]
+pub enum ChannelWebhook {
+  Table,
+  LastUsed,
+}
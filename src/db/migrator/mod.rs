@@ -5,6 +5,15 @@ use sea_orm_migration::prelude::*;
 
 // Add each migration file as a module
 mod m20221222_000001_initial;
+mod m20230103_000001_sticker_image;
+mod m20230110_000001_unique_pack_prefix;
+mod m20230114_000001_subscription_order;
+mod m20230118_000001_delete_original;
+mod m20230122_000001_sticker_uses;
+mod m20230125_000001_rate_limits;
+mod m20230128_000001_channel_webhook;
+mod m20230131_000001_webhook_last_used;
+mod m20230203_000001_post_as_bot;
 
 pub struct Migrator;
 
@@ -14,6 +23,15 @@ impl MigratorTrait for Migrator {
         vec![
             // Define the order of migrations.
             Box::new(m20221222_000001_initial::Migration),
+            Box::new(m20230103_000001_sticker_image::Migration),
+            Box::new(m20230110_000001_unique_pack_prefix::Migration),
+            Box::new(m20230114_000001_subscription_order::Migration),
+            Box::new(m20230118_000001_delete_original::Migration),
+            Box::new(m20230122_000001_sticker_uses::Migration),
+            Box::new(m20230125_000001_rate_limits::Migration),
+            Box::new(m20230128_000001_channel_webhook::Migration),
+            Box::new(m20230131_000001_webhook_last_used::Migration),
+            Box::new(m20230203_000001_post_as_bot::Migration),
         ]
     }
 }
@@ -27,7 +45,7 @@ pub async fn recreate(db: &DatabaseConnection) -> Result<(), DbErr> {
 #[allow(dead_code)]
 pub async fn update(db: &DatabaseConnection) -> Result<(), DbErr> {
   let pending = Migrator::get_pending_migrations(db).await?;
-  if pending.len() > 0 {
+  if !pending.is_empty() {
     info!("Applying {} pending migrations", pending.len());
     Migrator::up(db, None).await?;
   } else {
@@ -45,5 +63,8 @@ pub async fn check(db: &DatabaseConnection) -> Result<(), DbErr> {
   assert!(schema_manager.has_table("role").await?);
   assert!(schema_manager.has_table("user_pack_rel").await?);
   assert!(schema_manager.has_table("guild_pack_rel").await?);
+  assert!(schema_manager.has_table("channel_webhook").await?);
+  assert!(schema_manager.has_column("sticker", "image_source").await?);
+  assert!(schema_manager.has_column("channel_webhook", "last_used").await?);
   Ok(())
 }
\ No newline at end of file
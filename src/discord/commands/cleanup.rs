@@ -0,0 +1,32 @@
+use super::is_manager;
+use crate::discord::webhooks::cleanup_guild;
+use crate::errors::{ Error, Result };
+
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::interaction::application_command::ApplicationCommandInteraction,
+  prelude::*,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name("stickercleanup")
+    .description("Remove the bot's webhooks on this server that haven't been needed in a while")
+    .dm_permission(false)
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let guild = command.guild_id.ok_or(
+    Error::Other("Webhooks can only be cleaned up from within a server".to_string())
+  )?;
+  if !is_manager(ctx, command, guild).await? {
+    return Ok(Some("Only sticker managers can clean up webhooks".to_string()));
+  }
+  Ok(
+    Some(match cleanup_guild(ctx, guild).await? {
+      0 => "All sticker webhooks are still in use".to_string(),
+      1 => "Removed 1 webhook that wasn't needed anymore".to_string(),
+      n => format!("Removed {n} webhooks that weren't needed anymore"),
+    })
+  )
+}
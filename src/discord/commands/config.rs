@@ -0,0 +1,183 @@
+use super::{ bool_option, int_option, is_manager, role_option };
+use crate::CONFIG;
+use crate::discord::{ ratelimit::RateLimits, sticker_db };
+use crate::errors::{ Error, Result };
+
+use sea_orm::Set;
+use serenity::{
+  builder::{ CreateApplicationCommand, CreateApplicationCommandOption },
+  model::prelude::{
+    command::CommandOptionType,
+    interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::*,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  fn rate_limit<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    name: &str,
+    per: &str
+  ) -> &'a mut CreateApplicationCommandOption {
+    option
+      .name(name)
+      .description(format!("How many stickers {per} may post per minute, 0 for no limit"))
+      .kind(CommandOptionType::Integer)
+      .min_int_value(0)
+  }
+
+  command
+    .name("stickerconfig")
+    .description("Show or change how stickers work on this server")
+    .dm_permission(false)
+    .create_option(|option| {
+      option
+        .name("personal_stickers")
+        .description("Whether members may use their personal stickers and packs here")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| {
+      option
+        .name("manager_role")
+        .description("A role that may manage stickers without the Manage Server permission")
+        .kind(CommandOptionType::Role)
+    })
+    .create_option(|option| {
+      option
+        .name("remove_manager_role")
+        .description("Let only members with the Manage Server permission manage stickers")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| {
+      option
+        .name("delete_original")
+        .description("Whether to delete messages after posting their stickers")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| {
+      option
+        .name("post_as_bot")
+        .description("Whether to post stickers as the bot instead of through webhooks")
+        .kind(CommandOptionType::Boolean)
+    })
+    .create_option(|option| rate_limit(option, "user_rate_limit", "each member"))
+    .create_option(|option| rate_limit(option, "channel_rate_limit", "each channel"))
+    .create_option(|option| rate_limit(option, "guild_rate_limit", "the whole server"))
+}
+
+fn format_rate_limit(limit: u32) -> String {
+  match limit {
+    0 => "no limit".to_string(),
+    limit => format!("{limit} per minute"),
+  }
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let guild = command.guild_id.ok_or(
+    Error::Other("Stickers can only be configured from within a server".to_string())
+  )?;
+  if !is_manager(ctx, command, guild).await? {
+    return Ok(Some("Only sticker managers can configure stickers".to_string()));
+  }
+  let options = &command.data.options;
+  let personal_allowed = bool_option(options, "personal_stickers");
+  let delete_original = bool_option(options, "delete_original");
+  let post_as_bot = bool_option(options, "post_as_bot");
+  let manager_role = match role_option(options, "manager_role") {
+    Some(role) => Some(Some(role)),
+    None if bool_option(options, "remove_manager_role").unwrap_or(false) => Some(None),
+    None => None,
+  };
+  let rate_limit = |name| int_option(options, name).map(|l| l.clamp(0, u32::MAX as i64) as u32);
+  let user_rate_limit = rate_limit("user_rate_limit");
+  let channel_rate_limit = rate_limit("channel_rate_limit");
+  let guild_rate_limit = rate_limit("guild_rate_limit");
+
+  // otherwise the manager role could hand itself over to anyone
+  if
+    manager_role.is_some() &&
+    !command.member.as_ref().is_some_and(|m| m.permissions.is_some_and(|p| p.manage_guild()))
+  {
+    return Ok(Some("Only members with Manage Server can change the manager role".to_string()));
+  }
+
+  let db = sticker_db(ctx).await;
+  let changes = [
+    personal_allowed.is_some(),
+    manager_role.is_some(),
+    delete_original.is_some(),
+    post_as_bot.is_some(),
+    user_rate_limit.is_some(),
+    channel_rate_limit.is_some(),
+    guild_rate_limit.is_some(),
+  ];
+  let gd = if changes.contains(&true) {
+    db.update_guild_data(guild, |gd| {
+      if let Some(personal_allowed) = personal_allowed {
+        gd.personal_allowed = Set(personal_allowed);
+      }
+      if let Some(manager_role) = manager_role {
+        gd.manager_role = Set(manager_role.map(|r| r.0));
+      }
+      if delete_original.is_some() {
+        gd.delete_original = Set(delete_original);
+      }
+      if post_as_bot.is_some() {
+        gd.post_as_bot = Set(post_as_bot);
+      }
+      if user_rate_limit.is_some() {
+        gd.user_rate_limit = Set(user_rate_limit);
+      }
+      if channel_rate_limit.is_some() {
+        gd.channel_rate_limit = Set(channel_rate_limit);
+      }
+      if guild_rate_limit.is_some() {
+        gd.guild_rate_limit = Set(guild_rate_limit);
+      }
+    }).await?
+  } else {
+    db.ensure_guild_data(guild).await?
+  };
+
+  let yes_no = |setting: Option<bool>, key: &str| match setting {
+    Some(true) => "yes".to_string(),
+    Some(false) => "no".to_string(),
+    None => {
+      let default = CONFIG.get_bool(key).unwrap_or(false);
+      format!("{} (the bot's default)", if default { "yes" } else { "no" })
+    }
+  };
+  let delete_original = yes_no(gd.delete_original, "delete_original");
+  let post_as_bot = yes_no(gd.post_as_bot, "post_as_bot");
+  let mut channels: Vec<String> = db
+    .get_channel_webhooks(Some(guild)).await?
+    .iter()
+    .map(|wh| format!("<#{}>", wh.channel))
+    .collect();
+  channels.dedup();
+  let limits = RateLimits::for_guild(Some(&gd));
+  let lines = [
+    format!(
+      "Personal stickers and packs: {}",
+      if gd.personal_allowed { "allowed" } else { "not allowed" }
+    ),
+    format!(
+      "Manager role: {}",
+      gd.manager_role.map_or("none, only Manage Server".to_string(), |r| format!("<@&{r}>"))
+    ),
+    format!("Delete messages after posting their stickers: {delete_original}"),
+    format!("Post stickers as the bot instead of through webhooks: {post_as_bot}"),
+    format!(
+      "Rate limits: {} for each member, {} for each channel, {} for the whole server",
+      format_rate_limit(limits.user),
+      format_rate_limit(limits.channel),
+      format_rate_limit(limits.guild)
+    ),
+    if channels.is_empty() {
+      "Channels with sticker webhooks: none yet".to_string()
+    } else {
+      format!("Channels with sticker webhooks: {}", channels.join(", "))
+    },
+  ];
+  Ok(Some(lines.join("\n")))
+}
@@ -0,0 +1,377 @@
+mod cleanup;
+mod config;
+mod mysticker;
+mod pack;
+mod roles;
+mod st;
+mod sticker;
+
+use crate::CONFIG;
+use crate::discord::sticker_db;
+use crate::errors::{ Error, Result };
+use crate::stickers::{ LSticker, NewImage, StickerSource };
+
+use log::{ debug, info };
+use std::path::Path;
+
+use serenity::{
+  builder::{ CreateApplicationCommand, CreateApplicationCommandOption, CreateApplicationCommands },
+  json::Value,
+  model::prelude::{
+    command::{ Command, CommandOptionType },
+    interaction::{
+      application_command::{ ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue },
+      autocomplete::AutocompleteInteraction,
+    },
+    GuildId,
+    RoleId,
+  },
+  prelude::*,
+};
+use url::Url;
+
+/// Discord won't let bots upload anything bigger than this anyway
+const MAX_IMAGE_SIZE: u64 = 8 * 1024 * 1024;
+/// Discord's limit on message length
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+pub fn register(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+  commands
+    .create_application_command(st::register)
+    .create_application_command(sticker::register)
+    .create_application_command(mysticker::register)
+    .create_application_command(pack::register)
+    .create_application_command(roles::register)
+    .create_application_command(config::register)
+    .create_application_command(cleanup::register)
+}
+
+/// Registers the slash commands wherever `CONFIG` says: `global` everywhere (the default),
+/// `guilds` only in the `dev_guilds`, which is handy since those update immediately, or `both`.
+/// Commands that are already registered exactly like this are left alone.
+pub async fn sync(ctx: &Context) -> Result<()> {
+  let mode = CONFIG.get_string("command_registration").unwrap_or("global".to_string());
+  let (global, guilds) = match mode.as_str() {
+    "global" => (true, false),
+    "guilds" => (false, true),
+    "both" => (true, true),
+    _ => {
+      return Err(Error::Other(format!("Invalid command_registration: {mode}")));
+    }
+  };
+
+  let mut wanted = CreateApplicationCommands::default();
+  register(&mut wanted);
+
+  if global {
+    let existing = Command::get_global_application_commands(&ctx.http).await?;
+    if is_registered(&wanted.0, &existing) {
+      debug!("Global slash commands are up to date");
+    } else {
+      let commands = Command::set_global_application_commands(&ctx.http, register).await?;
+      info!("Registered {} global slash commands", commands.len());
+    }
+  }
+  if guilds {
+    for guild in CONFIG.get::<Vec<u64>>("dev_guilds").unwrap_or_default() {
+      let guild = GuildId(guild);
+      let existing = guild.get_application_commands(&ctx.http).await?;
+      if is_registered(&wanted.0, &existing) {
+        debug!("Slash commands for guild {guild} are up to date");
+      } else {
+        let commands = guild.set_application_commands(&ctx.http, register).await?;
+        info!("Registered {} slash commands for guild {guild}", commands.len());
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Whether the `existing` commands match the `wanted` ones, as built by `register`
+fn is_registered(wanted: &[Value], existing: &[Command]) -> bool {
+  wanted.len() == existing.len() &&
+    wanted.iter().all(|command| {
+      existing
+        .iter()
+        .any(|e| serde_json::to_value(e).is_ok_and(|e| is_subset(command, &e)))
+    })
+}
+
+/// Whether everything set in `wanted` has the same value in `actual`. Discord fills in
+/// ids and defaults we never set ourselves, so those don't count as differences.
+fn is_subset(wanted: &Value, actual: &Value) -> bool {
+  match (wanted, actual) {
+    (Value::Object(wanted), Value::Object(actual)) => {
+      wanted.iter().all(|(key, value)| {
+        match actual.get(key) {
+          Some(actual) => is_subset(value, actual),
+          None => value.is_null(),
+        }
+      })
+    }
+    (Value::Array(wanted), Value::Array(actual)) => {
+      wanted.len() == actual.len() && wanted.iter().zip(actual).all(|(w, a)| is_subset(w, a))
+    }
+    _ => wanted == actual,
+  }
+}
+
+/// Runs a slash command, returning the message the user should see (if any)
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Option<String> {
+  let result = match command.data.name.as_str() {
+    "st" => st::run(ctx, command).await,
+    "sticker" => sticker::run(ctx, command).await,
+    "mysticker" => mysticker::run(ctx, command).await,
+    "pack" => pack::run(ctx, command).await,
+    "stickerroles" => roles::run(ctx, command).await,
+    "stickerconfig" => config::run(ctx, command).await,
+    "stickercleanup" => cleanup::run(ctx, command).await,
+    _ => Ok(Some("not implemented :(".to_string())),
+  };
+  result.unwrap_or_else(|why| Some(format!("Error: {}", why)))
+}
+
+/// Suggests values for whichever option of a slash command the user is typing
+pub async fn autocomplete(ctx: &Context, autocomplete: &AutocompleteInteraction) -> Result<()> {
+  match autocomplete.data.name.as_str() {
+    "st" => st::autocomplete(ctx, autocomplete).await,
+    _ => Ok(()),
+  }
+}
+
+/// The name and options of the subcommand that was invoked
+fn subcommand(options: &[CommandDataOption]) -> Result<(&str, &[CommandDataOption])> {
+  match options.first() {
+    Some(sub) if sub.kind == CommandOptionType::SubCommand => {
+      Ok((sub.name.as_str(), sub.options.as_slice()))
+    }
+    _ => Err(Error::Other("Please pick a subcommand".to_string())),
+  }
+}
+
+fn option_value<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a CommandDataOptionValue> {
+  options.iter().find(|o| o.name == name)?.resolved.as_ref()
+}
+
+fn string_option(options: &[CommandDataOption], name: &str) -> Option<String> {
+  match option_value(options, name)? {
+    CommandDataOptionValue::String(value) => Some(value.clone()),
+    _ => None,
+  }
+}
+
+fn bool_option(options: &[CommandDataOption], name: &str) -> Option<bool> {
+  match option_value(options, name)? {
+    CommandDataOptionValue::Boolean(value) => Some(*value),
+    _ => None,
+  }
+}
+
+fn int_option(options: &[CommandDataOption], name: &str) -> Option<i64> {
+  match option_value(options, name)? {
+    CommandDataOptionValue::Integer(value) => Some(*value),
+    _ => None,
+  }
+}
+
+fn role_option(options: &[CommandDataOption], name: &str) -> Option<RoleId> {
+  match option_value(options, name)? {
+    CommandDataOptionValue::Role(role) => Some(role.id),
+    _ => None,
+  }
+}
+
+/// Like `string_option`, but for options Discord already enforces to be present
+fn required_string_option(options: &[CommandDataOption], name: &str) -> Result<String> {
+  string_option(options, name).ok_or(Error::Other(format!("Please provide a valid {name}")))
+}
+
+/// Reads the sticker image from either an uploaded `image` or an `url` option
+async fn image_option(options: &[CommandDataOption]) -> Result<NewImage> {
+  if let Some(CommandDataOptionValue::Attachment(attachment)) = option_value(options, "image") {
+    if !attachment.content_type.as_ref().is_some_and(|t| t.starts_with("image/")) {
+      return Err(Error::Other(format!("`{}` is not an image", attachment.filename)));
+    }
+    if attachment.size > MAX_IMAGE_SIZE {
+      return Err(Error::Other("Sticker images can be at most 8 MiB big".to_string()));
+    }
+    let extension = Path::new(&attachment.filename)
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .filter(|ext| ext.chars().all(|c| c.is_ascii_alphanumeric()))
+      .unwrap_or("png")
+      .to_ascii_lowercase();
+    return Ok(NewImage::Upload {
+      data: attachment.download().await?,
+      content_type: attachment.content_type.clone(),
+      extension,
+    });
+  }
+
+  if let Some(url) = string_option(options, "url") {
+    return match Url::parse(&url) {
+      Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => {
+        Ok(NewImage::External(parsed.into()))
+      }
+      _ => Err(Error::Other(format!("`{url}` is not a valid link"))),
+    };
+  }
+
+  Err(Error::Other("Please upload an image or provide a link to one".to_string()))
+}
+
+/// Adds the add/rename/remove/list subcommands for managing a set of stickers,
+/// with `owner` saying whose stickers they are (like "this server's").
+/// With `pack_option`, each subcommand also asks which pack it's about.
+fn create_management_options<'a>(
+  command: &'a mut CreateApplicationCommand,
+  owner: &str,
+  pack_option: bool
+) -> &'a mut CreateApplicationCommand {
+  fn pack(option: &mut CreateApplicationCommandOption, required: bool, enabled: bool) {
+    if enabled {
+      option.create_sub_option(|option| {
+        option
+          .name("pack")
+          .description("The prefix of the sticker pack")
+          .kind(CommandOptionType::String)
+          .required(required)
+      });
+    }
+  }
+
+  command
+    .create_option(|option| {
+      option.name("add").description(format!("Add a new sticker to {owner} stickers"));
+      pack(option.kind(CommandOptionType::SubCommand), true, pack_option);
+      option
+        .create_sub_option(|option| {
+          option
+            .name("name")
+            .description("The name of the new sticker")
+            .kind(CommandOptionType::String)
+            .required(true)
+        })
+        .create_sub_option(|option| {
+          option
+            .name("image")
+            .description("The sticker image")
+            .kind(CommandOptionType::Attachment)
+        })
+        .create_sub_option(|option| {
+          option
+            .name("url")
+            .description("A link to the sticker image, instead of uploading it")
+            .kind(CommandOptionType::String)
+        })
+    })
+    .create_option(|option| {
+      option.name("rename").description(format!("Rename one of {owner} stickers"));
+      pack(option.kind(CommandOptionType::SubCommand), true, pack_option);
+      option
+        .create_sub_option(|option| {
+          option
+            .name("name")
+            .description("The sticker to rename")
+            .kind(CommandOptionType::String)
+            .required(true)
+        })
+        .create_sub_option(|option| {
+          option
+            .name("new_name")
+            .description("The sticker's new name")
+            .kind(CommandOptionType::String)
+            .required(true)
+        })
+    })
+    .create_option(|option| {
+      option.name("remove").description(format!("Remove one of {owner} stickers"));
+      pack(option.kind(CommandOptionType::SubCommand), true, pack_option);
+      option.create_sub_option(|option| {
+        option
+          .name("name")
+          .description("The sticker to remove")
+          .kind(CommandOptionType::String)
+          .required(true)
+      })
+    })
+    .create_option(|option| {
+      option.name("list").description(format!("List {owner} stickers"));
+      pack(option.kind(CommandOptionType::SubCommand), false, pack_option);
+      option
+    })
+}
+
+/// Runs one of the subcommands from `create_management_options` that change stickers
+async fn run_management(
+  ctx: &Context,
+  command: &ApplicationCommandInteraction,
+  source: StickerSource,
+  sub: &str,
+  options: &[CommandDataOption]
+) -> Result<Option<String>> {
+  let db = sticker_db(ctx).await;
+  match sub {
+    "add" => {
+      let name = required_string_option(options, "name")?;
+      let image = image_option(options).await?;
+      let sticker = db.create_sticker(source, name, command.user.id, image).await?;
+      Ok(Some(format!("Added :{}:", sticker.name)))
+    }
+    "rename" => {
+      let name = required_string_option(options, "name")?;
+      let new_name = required_string_option(options, "new_name")?;
+      let sticker = db.rename_sticker(&source, &name, new_name).await?;
+      Ok(Some(format!("Renamed :{}: to :{}:", name, sticker.name)))
+    }
+    "remove" => {
+      let name = required_string_option(options, "name")?;
+      db.remove_sticker(&source, &name).await?;
+      Ok(Some(format!("Removed :{}:", name)))
+    }
+    _ => Ok(Some("not implemented :(".to_string())),
+  }
+}
+
+/// Whether the invoking member may manage the guild's stickers: anyone with
+/// Manage Server can, and so can the guild's configured manager role.
+async fn is_manager(
+  ctx: &Context,
+  command: &ApplicationCommandInteraction,
+  guild: GuildId
+) -> Result<bool> {
+  let member = match &command.member {
+    Some(member) => member,
+    None => {
+      return Ok(false);
+    }
+  };
+  if member.permissions.is_some_and(|p| p.manage_guild()) {
+    return Ok(true);
+  }
+  let manager_role = sticker_db(ctx).await
+    .get_guild_data(guild).await?
+    .and_then(|gd| gd.manager_role);
+  Ok(manager_role.is_some_and(|role| member.roles.contains(&RoleId(role))))
+}
+
+/// Lists sticker names, cutting the list short if it gets too long for one message
+fn format_sticker_list(title: &str, stickers: Vec<LSticker>) -> String {
+  let mut names: Vec<String> = stickers
+    .into_iter()
+    .map(|st| format!(":{}:", st.name))
+    .collect();
+  names.sort();
+
+  let mut list = format!("{title} ({}):", names.len());
+  for (i, name) in names.iter().enumerate() {
+    let more = format!("\n… and {} more", names.len() - i);
+    if list.len() + name.len() + more.len() + 1 > MAX_MESSAGE_LENGTH {
+      list.push_str(&more);
+      break;
+    }
+    list.push(' ');
+    list.push_str(name);
+  }
+  list
+}
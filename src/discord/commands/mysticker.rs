@@ -0,0 +1,33 @@
+use super::{ create_management_options, format_sticker_list, run_management, subcommand };
+use crate::discord::sticker_db;
+use crate::errors::Result;
+use crate::stickers::StickerSource;
+
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::interaction::application_command::ApplicationCommandInteraction,
+  prelude::*,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  create_management_options(
+    command.name("mysticker").description("Manage your personal stickers").dm_permission(true),
+    "your personal",
+    false
+  )
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let (sub, options) = subcommand(&command.data.options)?;
+
+  if sub == "list" {
+    let stickers = sticker_db(ctx).await.get_stickers_for_user(command.user.id).await?;
+    if stickers.is_empty() {
+      return Ok(Some("You don't have any personal stickers yet".to_string()));
+    }
+    return Ok(Some(format_sticker_list("Your personal stickers", stickers)));
+  }
+
+  // personal stickers are nobody's business but their owner's, so no further checks
+  run_management(ctx, command, StickerSource::User(command.user.id), sub, options).await
+}
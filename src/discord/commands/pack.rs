@@ -0,0 +1,183 @@
+use super::{
+  bool_option,
+  create_management_options,
+  format_sticker_list,
+  is_manager,
+  required_string_option,
+  run_management,
+  string_option,
+  subcommand,
+};
+use crate::db::entities::sticker_pack;
+use crate::discord::sticker_db;
+use crate::errors::{ Error, Result };
+use crate::stickers::StickerSource;
+
+use serenity::{
+  builder::{ CreateApplicationCommand, CreateApplicationCommandOption },
+  model::prelude::{
+    command::CommandOptionType,
+    interaction::application_command::ApplicationCommandInteraction,
+  },
+  prelude::*,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  fn pack(option: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    option.create_sub_option(|option| {
+      option
+        .name("pack")
+        .description("The prefix of the sticker pack")
+        .kind(CommandOptionType::String)
+        .required(true)
+    })
+  }
+  fn server(option: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    option.create_sub_option(|option| {
+      option
+        .name("server")
+        .description("Whether this is about the server's packs instead of your own")
+        .kind(CommandOptionType::Boolean)
+    })
+  }
+
+  create_management_options(
+    command.name("pack").description("Manage sticker packs").dm_permission(true),
+    "a pack's",
+    true
+  )
+    .create_option(|option| {
+      option
+        .name("create")
+        .description("Create a new sticker pack")
+        .kind(CommandOptionType::SubCommand)
+        .create_sub_option(|option| {
+          option
+            .name("prefix")
+            .description("The unique prefix of the new pack, like in :prefix.sticker:")
+            .kind(CommandOptionType::String)
+            .required(true)
+        })
+        .create_sub_option(|option| {
+          option
+            .name("display_name")
+            .description("A nicer name to show for the pack")
+            .kind(CommandOptionType::String)
+        })
+    })
+    .create_option(|option| {
+      pack(
+        option
+          .name("delete")
+          .description("Delete one of your sticker packs along with its stickers")
+          .kind(CommandOptionType::SubCommand)
+      )
+    })
+    .create_option(|option| {
+      server(
+        pack(
+          option
+            .name("subscribe")
+            .description("Start using a sticker pack")
+            .kind(CommandOptionType::SubCommand)
+        )
+      )
+    })
+    .create_option(|option| {
+      server(
+        pack(
+          option
+            .name("unsubscribe")
+            .description("Stop using a sticker pack")
+            .kind(CommandOptionType::SubCommand)
+        )
+      )
+    })
+}
+
+fn format_pack(pack: &sticker_pack::Model) -> String {
+  match &pack.display_name {
+    Some(name) => format!("`{}` ({})", pack.prefix, name),
+    None => format!("`{}`", pack.prefix),
+  }
+}
+
+fn format_pack_list(title: &str, packs: &[sticker_pack::Model]) -> String {
+  if packs.is_empty() {
+    return format!("{title}: none");
+  }
+  format!("{title}: {}", packs.iter().map(format_pack).collect::<Vec<_>>().join(", "))
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let (sub, options) = subcommand(&command.data.options)?;
+  let db = sticker_db(ctx).await;
+
+  match sub {
+    "create" => {
+      let prefix = required_string_option(options, "prefix")?;
+      let display_name = string_option(options, "display_name");
+      let pack = db.create_pack(prefix, display_name, command.user.id).await?;
+      Ok(Some(format!("Created the sticker pack {}", format_pack(&pack))))
+    }
+    "list" => {
+      if let Some(prefix) = string_option(options, "pack") {
+        let pack = db.get_pack(&prefix).await?;
+        let stickers = db.get_stickers_for_pack(pack.prefix.clone()).await?;
+        if stickers.is_empty() {
+          return Ok(Some(format!("The pack {} has no stickers yet", format_pack(&pack))));
+        }
+        return Ok(Some(format_sticker_list(&format!("Stickers in {}", format_pack(&pack)), stickers)));
+      }
+
+      let mut lines = vec![];
+      if let Some(guild) = command.guild_id {
+        lines.push(format_pack_list("Packs used by this server", &db.get_packs_for_guild(guild).await?));
+      }
+      lines.push(format_pack_list("Packs you use", &db.get_packs_for_user(command.user.id).await?));
+      lines.push(
+        format_pack_list("Packs you created", &db.get_packs_by_creator(command.user.id).await?)
+      );
+      Ok(Some(lines.join("\n")))
+    }
+    "subscribe" | "unsubscribe" => {
+      let pack = db.get_pack(&required_string_option(options, "pack")?).await?;
+      let subscribe = sub == "subscribe";
+
+      if bool_option(options, "server").unwrap_or(false) {
+        let guild = command.guild_id.ok_or(
+          Error::Other("Server packs can only be managed from within a server".to_string())
+        )?;
+        if !is_manager(ctx, command, guild).await? {
+          return Ok(Some("Only sticker managers can change this server's packs".to_string()));
+        }
+        if subscribe {
+          db.subscribe_guild(guild, &pack).await?;
+          return Ok(Some(format!("This server now uses the pack {}", format_pack(&pack))));
+        }
+        db.unsubscribe_guild(guild, &pack).await?;
+        return Ok(Some(format!("This server no longer uses the pack {}", format_pack(&pack))));
+      }
+
+      if subscribe {
+        db.subscribe_user(command.user.id, &pack).await?;
+        return Ok(Some(format!("You now use the pack {}", format_pack(&pack))));
+      }
+      db.unsubscribe_user(command.user.id, &pack).await?;
+      Ok(Some(format!("You no longer use the pack {}", format_pack(&pack))))
+    }
+    // everything else changes the pack itself, which only its creator may do
+    _ => {
+      let pack = db.get_pack(&required_string_option(options, "pack")?).await?;
+      if pack.creator != Some(command.user.id.0) {
+        return Ok(Some(format!("Only the creator of {} can change it", format_pack(&pack))));
+      }
+      if sub == "delete" {
+        let name = format_pack(&pack);
+        db.delete_pack(pack).await?;
+        return Ok(Some(format!("Deleted the sticker pack {name}")));
+      }
+      run_management(ctx, command, StickerSource::Pack(pack.id), sub, options).await
+    }
+  }
+}
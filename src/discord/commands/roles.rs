@@ -0,0 +1,126 @@
+use super::{ is_manager, role_option, subcommand };
+use crate::discord::sticker_db;
+use crate::errors::{ Error, Result };
+
+use serenity::{
+  builder::{ CreateApplicationCommand, CreateApplicationCommandOption },
+  model::prelude::{
+    command::CommandOptionType,
+    interaction::application_command::ApplicationCommandInteraction,
+    RoleId,
+  },
+  prelude::*,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  fn role(
+    option: &mut CreateApplicationCommandOption,
+    required: bool
+  ) -> &mut CreateApplicationCommandOption {
+    option.create_sub_option(|option| {
+      option
+        .name("role")
+        .description("The role")
+        .kind(CommandOptionType::Role)
+        .required(required)
+    })
+  }
+
+  command
+    .name("stickerroles")
+    .description("Choose which roles may use stickers on this server")
+    .dm_permission(false)
+    .create_option(|option| {
+      role(
+        option
+          .name("allow")
+          .description("Let a role use stickers; once any role is allowed, all others are not")
+          .kind(CommandOptionType::SubCommand),
+        true
+      )
+    })
+    .create_option(|option| {
+      role(
+        option
+          .name("deny")
+          .description("Keep a role from using stickers")
+          .kind(CommandOptionType::SubCommand),
+        true
+      )
+    })
+    .create_option(|option| {
+      option
+        .name("list")
+        .description("Show which roles may or may not use stickers")
+        .kind(CommandOptionType::SubCommand)
+    })
+    .create_option(|option| {
+      role(
+        option
+          .name("clear")
+          .description("Forget the rule for a role, or for all roles")
+          .kind(CommandOptionType::SubCommand),
+        false
+      )
+    })
+}
+
+fn format_roles(roles: &[RoleId]) -> String {
+  roles.iter().map(|r| format!("<@&{r}>")).collect::<Vec<_>>().join(", ")
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let guild = command.guild_id.ok_or(
+    Error::Other("Sticker roles can only be managed from within a server".to_string())
+  )?;
+  if !is_manager(ctx, command, guild).await? {
+    return Ok(Some("Only sticker managers can change who may use stickers".to_string()));
+  }
+  let (sub, options) = subcommand(&command.data.options)?;
+  let db = sticker_db(ctx).await;
+
+  match sub {
+    "allow" | "deny" => {
+      let role = role_option(options, "role").ok_or(
+        Error::Other("Please provide a valid role".to_string())
+      )?;
+      // @everyone shares the guild's id, but it's never among a member's roles
+      if role.0 == guild.0 {
+        return Ok(Some("@everyone can't have a sticker rule, please pick another role".to_string()));
+      }
+      let whitelisted = sub == "allow";
+      db.set_role_rule(guild, role, whitelisted).await?;
+      if whitelisted {
+        return Ok(Some(format!("<@&{role}> may now use stickers")));
+      }
+      Ok(Some(format!("<@&{role}> may no longer use stickers")))
+    }
+    "list" => {
+      let rules = db.get_role_rules(guild).await?;
+      let allowed: Vec<_> = rules.iter().filter(|r| r.whitelisted).map(|r| RoleId(r.id)).collect();
+      let denied: Vec<_> = rules.iter().filter(|r| !r.whitelisted).map(|r| RoleId(r.id)).collect();
+      let mut lines = vec![];
+      if allowed.is_empty() {
+        lines.push("Everyone may use stickers".to_string());
+      } else {
+        lines.push(format!("Only these roles may use stickers: {}", format_roles(&allowed)));
+      }
+      if !denied.is_empty() {
+        lines.push(format!("These roles may not use stickers: {}", format_roles(&denied)));
+      }
+      Ok(Some(lines.join("\n")))
+    }
+    "clear" => {
+      let role = role_option(options, "role");
+      let removed = db.clear_role_rules(guild, role).await?;
+      Ok(
+        Some(match role {
+          Some(role) if removed == 0 => format!("<@&{role}> had no sticker rule"),
+          Some(role) => format!("<@&{role}> has no sticker rule anymore"),
+          None => format!("Removed all {removed} sticker role rules"),
+        })
+      )
+    }
+    _ => Ok(Some("not implemented :(".to_string())),
+  }
+}
@@ -0,0 +1,80 @@
+use super::required_string_option;
+use crate::discord::{ send_sticker, sticker_db };
+use crate::errors::Result;
+
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::{
+    command::CommandOptionType,
+    interaction::{
+      application_command::ApplicationCommandInteraction,
+      autocomplete::AutocompleteInteraction,
+    },
+  },
+  prelude::*,
+};
+
+/// Discord shows at most this many autocomplete choices
+const MAX_CHOICES: usize = 25;
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  command
+    .name("st")
+    .description("Send a sticker")
+    .create_option(|option| {
+      option
+        .name("sticker")
+        .description("The sticker to send")
+        .kind(CommandOptionType::String)
+        .required(true)
+        .set_autocomplete(true)
+    })
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let sticker = required_string_option(&command.data.options, "sticker")?;
+  let member = command.member.as_ref();
+  match send_sticker(ctx.clone(), command.channel_id, sticker, command.user.id, member).await {
+    Ok(_) => Ok(None),
+    // no need to call it an error when the user just asked for something they can't have
+    Err(e) if e.is_user_error() => Ok(Some(e.to_string())),
+    Err(e) => Err(e),
+  }
+}
+
+/// Suggests the stickers the user can send, preferring names that start with what they
+/// typed so far over names that merely contain it, and popular stickers over rare ones.
+pub async fn autocomplete(ctx: &Context, autocomplete: &AutocompleteInteraction) -> Result<()> {
+  let typed = autocomplete.data.options
+    .iter()
+    .find(|o| o.focused)
+    .and_then(|o| o.value.as_ref())
+    .and_then(|v| v.as_str())
+    .unwrap_or_default()
+    .to_lowercase();
+
+  let available = sticker_db(ctx).await
+    .get_available_stickers(
+      autocomplete.user.id,
+      autocomplete.guild_id,
+      autocomplete.member.as_ref().map(|m| m.roles.as_slice())
+    ).await?;
+  let mut matches: Vec<_> = available
+    .into_iter()
+    .filter_map(|(reference, sticker)| {
+      let lower = reference.to_lowercase();
+      let prefix = lower.starts_with(&typed) || sticker.name.to_lowercase().starts_with(&typed);
+      (prefix || lower.contains(&typed)).then_some((!prefix, sticker.uses, reference))
+    })
+    .collect();
+  matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then_with(|| a.2.cmp(&b.2)));
+  matches.truncate(MAX_CHOICES);
+
+  autocomplete.create_autocomplete_response(&ctx.http, |response| {
+    for (_, _, reference) in &matches {
+      response.add_string_choice(reference, reference);
+    }
+    response
+  }).await?;
+  Ok(())
+}
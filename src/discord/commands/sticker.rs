@@ -0,0 +1,38 @@
+use super::{ create_management_options, format_sticker_list, is_manager, run_management, subcommand };
+use crate::discord::sticker_db;
+use crate::errors::{ Error, Result };
+use crate::stickers::StickerSource;
+
+use serenity::{
+  builder::CreateApplicationCommand,
+  model::prelude::interaction::application_command::ApplicationCommandInteraction,
+  prelude::*,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+  create_management_options(
+    command.name("sticker").description("Manage this server's stickers").dm_permission(false),
+    "this server's",
+    false
+  )
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<Option<String>> {
+  let guild = command.guild_id.ok_or(
+    Error::Other("Server stickers can only be managed from within a server".to_string())
+  )?;
+  let (sub, options) = subcommand(&command.data.options)?;
+
+  if sub == "list" {
+    let stickers = sticker_db(ctx).await.get_stickers_for_guild(guild).await?;
+    if stickers.is_empty() {
+      return Ok(Some("This server has no stickers yet".to_string()));
+    }
+    return Ok(Some(format_sticker_list("Stickers of this server", stickers)));
+  }
+
+  if !is_manager(ctx, command, guild).await? {
+    return Ok(Some("Only sticker managers can change this server's stickers".to_string()));
+  }
+  run_management(ctx, command, StickerSource::Guild(guild), sub, options).await
+}
@@ -0,0 +1,731 @@
+mod commands;
+mod ratelimit;
+mod webhooks;
+
+use crate::CONFIG;
+use crate::db::entities::channel_webhook;
+use crate::errors::{ Error, Result };
+use crate::stickers::{ LSticker, Resolution, StickerDatabase };
+use ratelimit::{ RateLimiter, RateLimits };
+use webhooks::{
+  avoid_webhooks,
+  evict_webhook,
+  forget_channel,
+  forget_guild,
+  pick_webhook,
+  webhook_cache,
+  WebhookCache,
+};
+
+use lazy_static::lazy_static;
+use log::{ debug, error, info, warn };
+use regex::Regex;
+use sea_orm::DatabaseConnection;
+use std::{
+  borrow::Cow,
+  collections::HashMap,
+  path::Path,
+  sync::Arc,
+  time::{ Duration, Instant },
+};
+
+use serenity::{
+  async_trait,
+  builder::ExecuteWebhook,
+  json::{ self, JsonMap, Value },
+  CacheAndHttp,
+  model::{
+    application::interaction::{ Interaction, InteractionResponseType },
+    channel::Message,
+    gateway::Ready,
+    prelude::*,
+  },
+  prelude::*,
+};
+
+struct StickerDb;
+impl TypeMapKey for StickerDb {
+  type Value = Arc<StickerDatabase<CacheAndHttp>>;
+}
+
+async fn sticker_db(ctx: &Context) -> Arc<StickerDatabase<CacheAndHttp>> {
+  let data_read = ctx.data.read().await;
+  data_read.get::<StickerDb>().expect("Expected to find the Sticker Database").clone()
+}
+
+struct StickerRateLimiter;
+impl TypeMapKey for StickerRateLimiter {
+  type Value = Arc<RateLimiter>;
+}
+
+/// Counts a sticker post against the rate limits of the user, the channel and the guild,
+/// failing with `Error::RateLimited` if one of them is used up
+async fn acquire_rate_limit(
+  ctx: &Context,
+  user: UserId,
+  channel: ChannelId,
+  guild: Option<GuildId>
+) -> Result<()> {
+  let gd = match guild {
+    Some(guild) => sticker_db(ctx).await.guild_settings(guild).await?,
+    None => None,
+  };
+  let limiter = ctx.data
+    .read().await
+    .get::<StickerRateLimiter>()
+    .expect("Expected to find the rate limiter")
+    .clone();
+  limiter.acquire(user, channel, guild, RateLimits::for_guild(gd.as_ref()))
+}
+
+struct Handler;
+
+#[async_trait]
+impl EventHandler for Handler {
+  async fn message(&self, ctx: Context, msg: Message) {
+    if msg.author.bot {
+      return;
+    }
+    match send_message_stickers(ctx, &msg).await {
+      Ok(()) => (),
+      Err(Error::MissingPermissions(perms)) => {
+        warn!("Missing permissions {} in channel {}", perms, msg.channel_id)
+      }
+      Err(why) => error!("Error sending stickers: {:?}", why),
+    }
+  }
+
+  async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+    if let Interaction::Autocomplete(autocomplete) = &interaction {
+      if let Err(why) = commands::autocomplete(&ctx, autocomplete).await {
+        error!("Error sending autocomplete choices: {:?}", why)
+      }
+      return;
+    }
+    if let Interaction::ApplicationCommand(command) = interaction {
+      debug!("Received command interaction: {:#?}", command);
+
+      let main_response = commands::run(&ctx, &command).await;
+
+      if
+        let Err(why) = command.create_interaction_response(&ctx.http, |response| {
+          response
+            .kind(InteractionResponseType::ChannelMessageWithSource)
+            .interaction_response_data(|message| {
+              message.content(main_response.unwrap_or("\u{2764}".to_string())).ephemeral(true)
+            })
+        }).await
+      {
+        error!("Error sending response: {:?}", why)
+      }
+    }
+  }
+
+  /// Webhooks of the channel were created, changed or deleted, which might include ours
+  async fn webhook_update(&self, ctx: Context, _guild_id: GuildId, channel: ChannelId) {
+    let cached: Vec<WebhookId> = match webhook_cache(&ctx).await.read().await.get(&channel) {
+      Some(pool) => pool.ids().collect(),
+      None => {
+        return;
+      }
+    };
+    let existing: Vec<WebhookId> = match channel.webhooks(&ctx.http).await {
+      Ok(webhooks) => webhooks.iter().map(|wh| wh.id).collect(),
+      // better look for them again than keep using webhooks that might be gone, but the
+      // request might just have failed, so the database keeps remembering them
+      Err(why) => {
+        debug!("Could not check the webhooks of channel {channel}: {why}");
+        webhook_cache(&ctx).await.write().await.remove(&channel);
+        return;
+      }
+    };
+    for webhook in cached {
+      if !existing.contains(&webhook) {
+        info!("Webhook {webhook} for channel {channel} was deleted");
+        evict_webhook(&ctx, channel, webhook).await;
+      }
+    }
+  }
+
+  async fn channel_delete(&self, ctx: Context, channel: &GuildChannel) {
+    if let Err(why) = forget_channel(&ctx, channel.id).await {
+      error!("Error forgetting the webhooks of channel {}: {:?}", channel.id, why);
+    }
+  }
+
+  async fn guild_delete(&self, ctx: Context, incomplete: UnavailableGuild, _full: Option<Guild>) {
+    // an outage makes guilds unavailable for a while, but the bot is still in them
+    if incomplete.unavailable {
+      return;
+    }
+    match forget_guild(&ctx, incomplete.id).await {
+      Ok(n) => info!("Left guild {}, forgot its {n} webhooks", incomplete.id),
+      Err(why) => error!("Error forgetting the webhooks of guild {}: {:?}", incomplete.id, why),
+    }
+  }
+
+  async fn ready(&self, ctx: Context, ready: Ready) {
+    info!("{} is connected!", ready.user.name);
+    ctx.online().await;
+    ctx.set_activity(Activity::watching("Sticker Surge die (#RIPBOZO)")).await;
+
+    if let Err(why) = commands::sync(&ctx).await {
+      error!("Error registering slash commands: {:?}", why);
+    }
+    webhooks::start_cleanup(&ctx);
+  }
+}
+
+/// How long to remember a member's guild avatar, since fetching it costs a request
+const GUILD_AVATAR_TTL: Duration = Duration::from_secs(60 * 60);
+/// Start forgetting expired guild avatars once this many are remembered
+const MAX_GUILD_AVATARS: usize = 10_000;
+
+/// The guild avatar of each member who posted lately (or `None` if they have none), along
+/// with when it was fetched. The bot doesn't get member updates, so these have to expire.
+type GuildAvatars = HashMap<(GuildId, UserId), (Instant, Option<String>)>;
+
+lazy_static! {
+  static ref GUILD_AVATARS: std::sync::Mutex<GuildAvatars> = Default::default();
+}
+
+struct WebhookIdentityDefinition {
+  username: String,
+  avatar_url: String,
+}
+impl WebhookIdentityDefinition {
+  #[allow(unused)]
+  fn new(username: String, avatar_url: String) -> Self {
+    Self {
+      username,
+      avatar_url,
+    }
+  }
+  /// How the member appears in their guild: their nickname and guild avatar if they set
+  /// them, their global name and avatar otherwise
+  fn from_member(member: &Member) -> Self {
+    Self {
+      username: member.display_name().into_owned(),
+      avatar_url: member.face(),
+    }
+  }
+  /// How the author of the message appears where they sent it. The message carries all of
+  /// that except for the guild avatar, which gets fetched (and remembered for a while).
+  async fn from_message(ctx: &Context, msg: &Message) -> Self {
+    let guild = match msg.guild_id {
+      Some(guild) => guild,
+      None => {
+        return Self::from_user(&msg.author);
+      }
+    };
+    Self {
+      username: msg.member
+        .as_ref()
+        .and_then(|member| member.nick.clone())
+        .unwrap_or_else(|| msg.author.name.clone()),
+      avatar_url: guild_avatar(ctx, guild, msg.author.id)
+        .await
+        .unwrap_or_else(|| msg.author.face()),
+    }
+  }
+  fn from_user(user: &User) -> Self {
+    Self {
+      username: user.name.to_owned(),
+      avatar_url: user.face(),
+    }
+  }
+  async fn from_uid(ctx: Context, uid: UserId) -> Result<Self> {
+    Ok(Self::from_user(&uid.to_user(ctx).await?))
+  }
+}
+
+impl Default for WebhookIdentityDefinition {
+  fn default() -> Self {
+    Self {
+      username: "Sticky Surgery".to_string(),
+      avatar_url: "Sticky Surgery avatar url".to_string(),
+    }
+  }
+}
+
+/// The member's guild avatar, if they set one
+async fn guild_avatar(ctx: &Context, guild: GuildId, user: UserId) -> Option<String> {
+  if let Some(member) = ctx.cache.member(guild, user) {
+    return member.avatar_url();
+  }
+  if let Some((fetched, avatar)) = GUILD_AVATARS.lock().unwrap().get(&(guild, user)) {
+    if fetched.elapsed() < GUILD_AVATAR_TTL {
+      return avatar.clone();
+    }
+  }
+  let avatar = match ctx.http.get_member(guild.0, user.0).await {
+    Ok(member) => member.avatar_url(),
+    Err(why) => {
+      debug!("Could not find member {user} of guild {guild}: {why}");
+      None
+    }
+  };
+  let mut avatars = GUILD_AVATARS.lock().unwrap();
+  if avatars.len() >= MAX_GUILD_AVATARS {
+    avatars.retain(|_, (fetched, _)| fetched.elapsed() < GUILD_AVATAR_TTL);
+  }
+  avatars.insert((guild, user), (Instant::now(), avatar.clone()));
+  avatar
+}
+
+pub async fn init(db: Arc<DatabaseConnection>) -> Result<()> {
+  // Bot permissions: 415001537536
+  let token = CONFIG.get_string("discord_token").expect("Expected a token in the environment");
+  let intents =
+    GatewayIntents::non_privileged() |
+    GatewayIntents::GUILD_WEBHOOKS |
+    GatewayIntents::GUILD_MESSAGES |
+    GatewayIntents::DIRECT_MESSAGES |
+    GatewayIntents::MESSAGE_CONTENT;
+
+  let mut client = Client::builder(token, intents)
+    .event_handler(Handler).await
+    .expect("Err creating client");
+
+  {
+    // Initialize the client's global data store
+    let mut data = client.data.write().await;
+
+    let sticker_db = Arc::new(StickerDatabase::new(db, client.cache_and_http.clone()));
+    data.insert::<WebhookCache>(Arc::new(RwLock::new(webhooks::load_pools(&sticker_db).await?)));
+    data.insert::<StickerDb>(sticker_db);
+    data.insert::<StickerRateLimiter>(Arc::new(RateLimiter::default()));
+    // data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
+  }
+  client.start().await?;
+  Ok(())
+}
+
+/// Discord's limit on attachments per message
+const MAX_ATTACHMENTS: usize = 10;
+/// Discord shows at most 10 embeds per message, and the quoted reply and the credit
+/// for the user might need one each
+const MAX_LINKED_STICKERS: usize = 8;
+
+/// How a sticker image gets into a message
+enum StickerImage {
+  /// Uploaded along with the message
+  File(AttachmentType<'static>),
+  /// Shown in an embed, so that Discord fetches the image and not the bot, which could
+  /// otherwise be tricked into posting whatever some internal address answers
+  Link(String),
+}
+
+/// Stickers stored locally get uploaded, external ones linked
+async fn sticker_image(sticker: &LSticker) -> Result<StickerImage> {
+  // hosted stickers are only uploaded when we aren't serving them ourselves
+  if let Some(url) = sticker.image_url() {
+    return Ok(StickerImage::Link(url));
+  }
+  let path = match sticker.image_path() {
+    Some(path) => path,
+    None => {
+      return Err(Error::Other(format!("Sticker {} has no image", sticker.name)));
+    }
+  };
+  let extension = Path::new(&path).extension().and_then(|ext| ext.to_str()).unwrap_or("png");
+  Ok(
+    StickerImage::File(AttachmentType::Bytes {
+      filename: format!("{}.{}", sticker.name, extension),
+      data: Cow::Owned(tokio::fs::read(&path).await?),
+    })
+  )
+}
+
+/// Posts a sticker for `user`, who is the `member` if this is in a guild
+async fn send_sticker(
+  ctx: Context,
+  channel: ChannelId,
+  sticker: String,
+  user: UserId,
+  member: Option<&Member>
+) -> Result<Option<Message>> {
+  let guild = member.map(|m| m.guild_id);
+  let roles = member.map(|m| m.roles.as_slice());
+  let db = sticker_db(&ctx).await;
+  let resolved = db.resolve_sticker(sticker.clone(), user, guild, roles).await?;
+  info!("sticker resolution gave: {:?}", resolved);
+  let sticker = match resolved.into_result(&sticker) {
+    Err(Error::StickerNotFound { reference, .. }) => {
+      let suggestions = db.suggest_stickers(&reference, user, guild, roles).await?;
+      return Err(Error::StickerNotFound { reference, suggestions });
+    }
+    result => result?,
+  };
+  acquire_rate_limit(&ctx, user, channel, guild).await?;
+
+  let message = send_for_user(
+    ctx.clone(),
+    channel,
+    guild,
+    None,
+    match member {
+      Some(member) => WebhookIdentityDefinition::from_member(member),
+      None => WebhookIdentityDefinition::from_uid(ctx.clone(), user).await?,
+    },
+    vec![sticker_image(&sticker).await?],
+    None
+  ).await?;
+  db.record_use(&sticker).await?;
+  Ok(message)
+}
+
+/// Posts all stickers referenced in a message like `lol :bigbrain: :headpats:` in one go.
+/// If the original message gets deleted afterwards, its remaining text is reposted along
+/// with the stickers; otherwise only the stickers are posted.
+async fn send_message_stickers(ctx: Context, msg: &Message) -> Result<()> {
+  lazy_static! {
+    // the optional `<a?` catches Discord's own custom emoji, like `<:name:id>`. Names can't
+    // start or end with a space, so that a colon in the text (like in `note: see :bigbrain:`)
+    // doesn't pair up with the one opening a sticker.
+    static ref RE: Regex = Regex::new(
+      r"(<a?)?:((?:[a-z0-9\-_]+\.)?[a-zA-Z0-9\-_+](?:[a-zA-Z0-9\-_+ ]*[a-zA-Z0-9\-_+])?):"
+    ).unwrap();
+  }
+  if !RE.is_match(&msg.content) {
+    return Ok(());
+  }
+
+  let db = sticker_db(&ctx).await;
+  let roles = msg.member.as_ref().map(|m| m.roles.as_slice());
+  let mut stickers: Vec<(String, LSticker)> = vec![];
+  let mut missing: Vec<String> = vec![];
+  let mut content = String::new();
+  let mut last_end = 0;
+  let mut start = 0;
+  let mut locations = RE.capture_locations();
+  while let Some(found) = RE.captures_read_at(&mut locations, &msg.content, start) {
+    let range = found.range();
+    if locations.get(1).is_some() {
+      start = range.end;
+      continue;
+    }
+    let (name_start, name_end) = locations.get(2).unwrap();
+    let reference = msg.content[name_start..name_end].to_owned();
+    let used = stickers.iter().any(|(r, _)| *r == reference) || match
+      db.resolve_sticker(reference.clone(), msg.author.id, msg.guild_id, roles).await?
+    {
+      Resolution::Found(sticker) => {
+        // stickers with a public URL are linked in embeds, the others uploaded as attachments
+        let linked = sticker.image_url().is_some();
+        let limit = if linked { MAX_LINKED_STICKERS } else { MAX_ATTACHMENTS };
+        let fits =
+          stickers.iter().filter(|(_, s)| s.image_url().is_some() == linked).count() < limit;
+        if fits {
+          stickers.push((reference, sticker));
+        }
+        fits
+      }
+      // probably not meant as a sticker at all, so leave the text alone
+      Resolution::NotFound => {
+        missing.push(reference);
+        false
+      }
+      // users who may not use stickers here just get their text posted as it is
+      _ => false,
+    };
+    if !used {
+      // the closing colon might open the next reference, like in `at 10:30:wave:`
+      start = range.end - 1;
+      continue;
+    }
+    content.push_str(&msg.content[last_end..range.start]);
+    last_end = range.end;
+    start = range.end;
+  }
+  content.push_str(&msg.content[last_end..]);
+  info!("Stickers requested: {:?}", stickers.iter().map(|(r, _)| r).collect::<Vec<_>>());
+  if stickers.is_empty() {
+    return report_missing_stickers(&ctx, msg, &missing, false).await;
+  }
+
+  match acquire_rate_limit(&ctx, msg.author.id, msg.channel_id, msg.guild_id).await {
+    Ok(()) => (),
+    Err(Error::RateLimited(scope, wait)) => {
+      debug!("Dropping stickers of message {}: {:?} is rate limited for {:?}", msg.id, scope, wait);
+      if CONFIG.get_string("rate_limit_feedback").is_ok_and(|f| f == "react") {
+        msg
+          .react(&ctx, '\u{23f3}').await
+          .map_err(|e| Error::from_serenity_with_permissions(e, Permissions::ADD_REACTIONS))?;
+      }
+      return Ok(());
+    }
+    Err(e) => {
+      return Err(e);
+    }
+  }
+
+  let delete_original = match msg.guild_id {
+    Some(guild) => db.guild_settings(guild).await?.and_then(|gd| gd.delete_original),
+    None => None,
+  }.unwrap_or(CONFIG.get_bool("delete_original").unwrap_or(false));
+  let content = content.trim();
+  let content = (delete_original && !content.is_empty()).then(|| content.to_string());
+  let mut images = vec![];
+  for (_, sticker) in &stickers {
+    images.push(sticker_image(sticker).await?);
+  }
+
+  send_for_user(
+    ctx.clone(),
+    msg.channel_id,
+    msg.guild_id,
+    content,
+    WebhookIdentityDefinition::from_message(&ctx, msg).await,
+    images,
+    msg.referenced_message.as_deref()
+  ).await?;
+  for (_, sticker) in &stickers {
+    db.record_use(sticker).await?;
+  }
+
+  // the stickers are out already, so a failure here only means the original stays around
+  if delete_original {
+    msg
+      .delete(&ctx).await
+      .map_err(|e| Error::from_serenity_with_permissions(e, Permissions::MANAGE_MESSAGES))?;
+  }
+  report_missing_stickers(&ctx, msg, &missing, delete_original).await
+}
+
+/// Lets the author know about sticker references that look like typos, as configured by
+/// `missing_sticker_feedback`: `react` to the message, send a `dm`, or do nothing (`none`).
+/// References without any similar sticker are most likely not meant as stickers at all,
+/// so they are never reported.
+async fn report_missing_stickers(
+  ctx: &Context,
+  msg: &Message,
+  missing: &[String],
+  deleted: bool
+) -> Result<()> {
+  let feedback = CONFIG.get_string("missing_sticker_feedback").unwrap_or("none".to_string());
+  if missing.is_empty() || feedback == "none" {
+    return Ok(());
+  }
+
+  let db = sticker_db(ctx).await;
+  let mut lines = vec![];
+  for reference in missing {
+    let suggestions = db.suggest_stickers(
+      reference,
+      msg.author.id,
+      msg.guild_id,
+      msg.member.as_ref().map(|m| m.roles.as_slice())
+    ).await?;
+    if !suggestions.is_empty() {
+      lines.push(Error::StickerNotFound { reference: reference.clone(), suggestions }.to_string());
+    }
+  }
+  if lines.is_empty() {
+    return Ok(());
+  }
+
+  match feedback.as_str() {
+    // there's nothing left to react to if the message is gone
+    "react" if !deleted => {
+      msg
+        .react(ctx, '\u{2753}').await
+        .map_err(|e| Error::from_serenity_with_permissions(e, Permissions::ADD_REACTIONS))?;
+    }
+    "dm" => {
+      msg.author.direct_message(ctx, |m| m.content(lines.join("\n"))).await?;
+    }
+    _ => (),
+  }
+  Ok(())
+}
+
+/// Posts for a user through a webhook, or as the bot where the guild wants that (as configured
+/// by `post_as_bot`) or webhooks can't be used: in DMs, and in channels where the bot may not
+/// manage webhooks
+async fn send_for_user(
+  ctx: Context,
+  channel: ChannelId,
+  guild: Option<GuildId>,
+  content: Option<String>,
+  user: WebhookIdentityDefinition,
+  images: Vec<StickerImage>,
+  reply: Option<&Message>
+) -> Result<Option<Message>> {
+  let mut attachments = vec![];
+  let mut links = vec![];
+  for image in images {
+    match image {
+      StickerImage::File(attachment) => attachments.push(attachment),
+      StickerImage::Link(url) => links.push(url),
+    }
+  }
+  let post_as_bot = match guild {
+    Some(guild) => {
+      sticker_db(&ctx).await.guild_settings(guild).await?.and_then(|gd| gd.post_as_bot)
+    }
+    None => Some(true),
+  }.unwrap_or(CONFIG.get_bool("post_as_bot").unwrap_or(false));
+  if !post_as_bot {
+    let embeds = reply
+      .map(|reply| reply_embed(reply, guild))
+      .into_iter()
+      .chain(links.iter().map(|url| Embed::fake(|e| e.image(url))))
+      .collect();
+    let sent = send_as_webhook(
+      ctx.clone(),
+      channel,
+      content.clone(),
+      &user,
+      attachments.clone(),
+      embeds
+    ).await;
+    match sent {
+      Err(e) if e.is_webhook_unusable() => {
+        debug!("Can't use webhooks in channel {channel} ({e}), posting as the bot instead");
+      }
+      result => {
+        return result;
+      }
+    }
+  }
+  Ok(Some(send_as_bot(&ctx, channel, content, &user, attachments, &links, reply).await?))
+}
+
+/// Posts as the bot itself, crediting the user with an embed. Unlike webhooks, the bot can
+/// reply to messages for real.
+async fn send_as_bot(
+  ctx: &Context,
+  channel: ChannelId,
+  content: Option<String>,
+  user: &WebhookIdentityDefinition,
+  attachments: Vec<AttachmentType<'_>>,
+  links: &[String],
+  reply: Option<&Message>
+) -> Result<Message> {
+  channel
+    .send_message(ctx, |m| {
+      if let Some(c) = content {
+        m.content(c);
+      }
+      if let Some(reply) = reply {
+        m.reference_message(reply);
+      }
+      m.add_files(attachments)
+        .add_embed(|e| e.author(|a| a.name(&user.username).icon_url(&user.avatar_url)))
+        .allowed_mentions(|mentions| mentions.empty_parse().replied_user(false));
+      for url in links {
+        m.add_embed(|e| e.image(url));
+      }
+      m
+    }).await
+    .map_err(|e| {
+      Error::from_serenity_with_permissions(
+        e,
+        Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES | Permissions::EMBED_LINKS
+      )
+    })
+}
+
+/// Webhooks only exist on regular channels, so messages for a thread have to go
+/// through its parent channel's webhook. Returns the webhook channel and the thread, if any.
+async fn webhook_target(ctx: &Context, channel: ChannelId) -> Result<(ChannelId, Option<ChannelId>)> {
+  match channel.to_channel(ctx).await? {
+    Channel::Guild(gc) if
+      matches!(gc.kind, ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread)
+    => {
+      let parent = gc.parent_id.ok_or(Error::Other(format!("Thread {channel} has no parent")))?;
+      Ok((parent, Some(channel)))
+    }
+    _ => Ok((channel, None)),
+  }
+}
+
+/// Webhooks can't reply to messages, so quote the message being replied to instead
+fn reply_embed(reply: &Message, guild: Option<GuildId>) -> Value {
+  const MAX_QUOTE_LENGTH: usize = 200;
+  let mut quote: String = reply.content.chars().take(MAX_QUOTE_LENGTH).collect();
+  if quote.len() < reply.content.len() {
+    quote.push('…');
+  }
+  let link = reply.id.link(reply.channel_id, guild);
+  Embed::fake(|e| {
+    e.author(|a| a.name(&reply.author.name).icon_url(reply.author.face())).description(
+      format!("{quote}\n[Jump to message]({link})")
+    )
+  })
+}
+
+async fn send_as_webhook(
+  ctx: Context,
+  channel: ChannelId,
+  content: Option<String>,
+  user: &WebhookIdentityDefinition,
+  attachments: Vec<AttachmentType<'_>>,
+  embeds: Vec<Value>
+) -> Result<Option<Message>> {
+  let (webhook_channel, thread) = webhook_target(&ctx, channel).await?;
+  let webhook = pick_webhook(&ctx, webhook_channel).await?;
+
+  let mut execute = ExecuteWebhook::default();
+  // reposted text shouldn't ping anyone a second time, let alone @everyone
+  execute
+    .username(&user.username)
+    .avatar_url(&user.avatar_url)
+    .allowed_mentions(|m| m.empty_parse());
+  if let Some(c) = content {
+    execute.content(c);
+  }
+  if !embeds.is_empty() {
+    execute.embeds(embeds);
+  }
+  let map = json::hashmap_to_json_map(execute.0);
+
+  match execute_webhook(&ctx, &webhook, thread, attachments.clone(), &map).await {
+    // someone deleted the webhook, so use (or make) another one and try once more
+    Err(e) if e.is_unknown_webhook() => {
+      warn!("Webhook {} for channel {webhook_channel} is gone", webhook.id);
+      evict_webhook(&ctx, webhook_channel, WebhookId(webhook.id)).await;
+      let webhook = pick_webhook(&ctx, webhook_channel).await?;
+      execute_webhook(&ctx, &webhook, thread, attachments, &map).await
+    }
+    Err(e @ Error::MissingPermissions(_)) => {
+      avoid_webhooks(webhook_channel);
+      Err(e)
+    }
+    result => result,
+  }
+}
+
+async fn execute_webhook(
+  ctx: &Context,
+  webhook: &channel_webhook::Model,
+  thread: Option<ChannelId>,
+  attachments: Vec<AttachmentType<'_>>,
+  map: &JsonMap
+) -> Result<Option<Message>> {
+  let token = webhook_token(webhook, thread);
+  let message = if attachments.is_empty() {
+    ctx.http.execute_webhook(webhook.id, &token, false, map).await
+  } else {
+    ctx.http.execute_webhook_with_files(webhook.id, &token, false, attachments, map).await
+  }.map_err(|e| Error::from_serenity_with_permissions(e, Permissions::MANAGE_WEBHOOKS))?;
+  if let Err(why) = sticker_db(ctx).await.touch_channel_webhook(WebhookId(webhook.id)).await {
+    warn!("Could not note the use of webhook {}: {why}", webhook.id);
+  }
+  Ok(message)
+}
+
+/// The token to execute the webhook with, which also smuggles in the `thread` to post in
+fn webhook_token(webhook: &channel_webhook::Model, thread: Option<ChannelId>) -> String {
+  let token = &webhook.token;
+  // serenity 0.11 can't execute webhooks in threads, so the thread_id gets slipped into the
+  // URL along with the token. The trailing `#` turns the `?wait=` serenity appends after the
+  // token into a fragment, which never makes it into the request.
+  // This relies on how serenity 0.11.5 builds the URL (`Route::webhook_with_token_optioned`),
+  // which is why Cargo.toml pins that exact version.
+  match thread {
+    Some(thread) => format!("{token}?wait=false&thread_id={thread}#"),
+    None => token.clone(),
+  }
+}
@@ -0,0 +1,186 @@
+use crate::CONFIG;
+use crate::db::entities::guild_data;
+use crate::errors::{ Error, RateLimitScope, Result };
+
+use serenity::model::prelude::{ ChannelId, GuildId, UserId };
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{ Duration, Instant };
+
+/// Start forgetting about idle buckets once there are this many
+const MAX_BUCKETS: usize = 10_000;
+
+/// How many sticker posts per minute each scope gets, with 0 meaning no limit
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimits {
+  pub user: u32,
+  pub channel: u32,
+  pub guild: u32,
+}
+impl RateLimits {
+  /// The guild's limits, falling back to the configured `rate_limits` where it has none
+  pub fn for_guild(gd: Option<&guild_data::Model>) -> Self {
+    let default = |key: &str, fallback: u32| {
+      CONFIG.get_int(&format!("rate_limits.{key}")).map_or(fallback, |l| l.max(0) as u32)
+    };
+    Self {
+      user: gd.and_then(|gd| gd.user_rate_limit).unwrap_or_else(|| default("user", 10)),
+      channel: gd.and_then(|gd| gd.channel_rate_limit).unwrap_or_else(|| default("channel", 30)),
+      guild: gd.and_then(|gd| gd.guild_rate_limit).unwrap_or_else(|| default("guild", 60)),
+    }
+  }
+}
+
+/// A token bucket that holds up to a minute's worth of posts and refills continuously
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+}
+impl Bucket {
+  fn refill(&mut self, per_minute: u32, now: Instant) {
+    let refilled = now.duration_since(self.updated).as_secs_f64() * (per_minute as f64) / 60.0;
+    self.tokens = (self.tokens + refilled).min(per_minute as f64);
+    self.updated = now;
+  }
+
+  /// How long until the bucket has a token again
+  fn wait_time(&self, per_minute: u32) -> Duration {
+    Duration::from_secs_f64(((1.0 - self.tokens) * 60.0 / (per_minute as f64)).max(0.0))
+  }
+}
+
+/// Keeps track of sticker posts, so users can't flood channels (and the webhooks) with them
+#[derive(Default)]
+pub struct RateLimiter {
+  buckets: Mutex<HashMap<RateLimitScope, Bucket>>,
+}
+impl RateLimiter {
+  /// Takes a post from the user's, the channel's and the guild's buckets, or fails with
+  /// `Error::RateLimited` without taking anything if one of them is empty
+  pub fn acquire(
+    &self,
+    user: UserId,
+    channel: ChannelId,
+    guild: Option<GuildId>,
+    limits: RateLimits
+  ) -> Result<()> {
+    self.acquire_at(user, channel, guild, limits, Instant::now())
+  }
+
+  fn acquire_at(
+    &self,
+    user: UserId,
+    channel: ChannelId,
+    guild: Option<GuildId>,
+    limits: RateLimits,
+    now: Instant
+  ) -> Result<()> {
+    let mut scopes = vec![
+      (RateLimitScope::User(user), limits.user),
+      (RateLimitScope::Channel(channel), limits.channel),
+    ];
+    if let Some(guild) = guild {
+      scopes.push((RateLimitScope::Guild(guild), limits.guild));
+    }
+    scopes.retain(|(_, per_minute)| *per_minute > 0);
+
+    let mut buckets = self.buckets.lock().unwrap();
+    for (scope, per_minute) in &scopes {
+      let bucket = buckets
+        .entry(*scope)
+        .or_insert(Bucket { tokens: *per_minute as f64, updated: now });
+      bucket.refill(*per_minute, now);
+      if bucket.tokens < 1.0 {
+        return Err(Error::RateLimited(*scope, bucket.wait_time(*per_minute)));
+      }
+    }
+    for (scope, _) in &scopes {
+      if let Some(bucket) = buckets.get_mut(scope) {
+        bucket.tokens -= 1.0;
+      }
+    }
+
+    // buckets that have been idle for a minute are full again, so they can go
+    if buckets.len() > MAX_BUCKETS {
+      buckets.retain(|_, bucket| now.duration_since(bucket.updated) < Duration::from_secs(60));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ RateLimiter, RateLimits };
+  use crate::errors::{ Error, RateLimitScope, Result };
+  use serenity::model::prelude::{ ChannelId, GuildId, UserId };
+  use std::time::{ Duration, Instant };
+
+  const LIMITS: RateLimits = RateLimits { user: 3, channel: 5, guild: 0 };
+
+  fn limited_scope(result: Result<()>) -> Option<RateLimitScope> {
+    match result {
+      Ok(()) => None,
+      Err(Error::RateLimited(scope, _)) => Some(scope),
+      Err(e) => panic!("unexpected error {e}"),
+    }
+  }
+
+  #[test]
+  fn allows_a_burst_of_a_minutes_worth() {
+    let limiter = RateLimiter::default();
+    let now = Instant::now();
+    let post = || limited_scope(limiter.acquire_at(UserId(1), ChannelId(2), None, LIMITS, now));
+    assert_eq!(post(), None);
+    assert_eq!(post(), None);
+    assert_eq!(post(), None);
+    assert_eq!(post(), Some(RateLimitScope::User(UserId(1))));
+  }
+
+  #[test]
+  fn refills_over_time() {
+    let limiter = RateLimiter::default();
+    let start = Instant::now();
+    let post = |secs| {
+      let now = start + Duration::from_secs(secs);
+      limiter.acquire_at(UserId(1), ChannelId(2), None, LIMITS, now)
+    };
+    for _ in 0..3 {
+      post(0).unwrap();
+    }
+    // three per minute is one every 20 seconds
+    match post(5) {
+      Err(Error::RateLimited(_, wait)) => assert_eq!(wait.as_secs_f64().round(), 15.0),
+      other => panic!("expected a rate limit, got {other:?}"),
+    }
+    assert!(post(20).is_ok());
+    assert!(post(30).is_err());
+    // idling for longer than a minute doesn't allow more than the usual burst
+    for _ in 0..3 {
+      post(600).unwrap();
+    }
+    assert!(post(600).is_err());
+  }
+
+  #[test]
+  fn keeps_scopes_apart() {
+    let limiter = RateLimiter::default();
+    let now = Instant::now();
+    let post = |user, channel, guild| {
+      limited_scope(limiter.acquire_at(UserId(user), ChannelId(channel), guild, LIMITS, now))
+    };
+    for _ in 0..3 {
+      assert_eq!(post(1, 10, None), None);
+    }
+    assert_eq!(post(1, 10, None), Some(RateLimitScope::User(UserId(1))));
+    // other users still have their own posts, until the channel runs out
+    assert_eq!(post(2, 10, None), None);
+    assert_eq!(post(3, 10, None), None);
+    assert_eq!(post(4, 10, None), Some(RateLimitScope::Channel(ChannelId(10))));
+    // being limited in one scope didn't cost anything in the others
+    assert_eq!(post(4, 11, None), None);
+    // and a guild limit of 0 means no limit at all
+    for user in 5..20 {
+      assert_eq!(post(user, user + 100, Some(GuildId(100))), None);
+    }
+  }
+}
@@ -0,0 +1,428 @@
+use crate::CONFIG;
+use crate::db::entities::channel_webhook;
+use crate::discord::sticker_db;
+use crate::errors::{ Error, Result };
+use crate::stickers::StickerDatabase;
+
+use lazy_static::lazy_static;
+use log::{ debug, error, info, warn };
+use std::{
+  collections::HashMap,
+  sync::{ atomic::{ AtomicBool, Ordering }, Arc },
+  time::{ Duration, Instant, SystemTime },
+};
+
+use serenity::{
+  http::{ routing::Route, CacheHttp },
+  model::{ prelude::*, webhook::Webhook },
+  prelude::*,
+};
+
+/// Discord won't let a channel have more webhooks than this
+const MAX_CHANNEL_WEBHOOKS: usize = 15;
+/// How long to leave webhooks alone in a channel where the bot lacked the permissions for
+/// them, posting as the bot instead, before trying them again
+const MISSING_PERMISSIONS_RETRY: Duration = Duration::from_secs(10 * 60);
+/// What all webhooks the bot makes are called, followed by the channel they're for
+const WEBHOOK_PREFIX: &str = "stickysurgery-";
+
+/// `ready` fires again after every reconnect, but the cleanup only needs to run once
+static CLEANUP_STARTED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+  /// Only one pool gets created at a time, so that two posts in a new channel don't both
+  /// make webhooks for it. Posts in channels that have their pool already don't wait for this.
+  static ref POOL_CREATION: Mutex<()> = Mutex::new(());
+  /// Channels where webhooks can't be used for now, and until when
+  static ref WITHOUT_WEBHOOKS: std::sync::Mutex<HashMap<ChannelId, Instant>> = Default::default();
+}
+
+pub struct WebhookCache;
+impl TypeMapKey for WebhookCache {
+  type Value = Arc<RwLock<HashMap<ChannelId, WebhookPool>>>;
+}
+
+/// The webhooks the bot posts through in one channel, taking turns so that a busy channel
+/// isn't held up by the rate limit of a single webhook
+#[derive(Default)]
+pub struct WebhookPool {
+  webhooks: Vec<channel_webhook::Model>,
+  next: usize,
+}
+impl WebhookPool {
+  /// The next webhook in turn that isn't `busy` until some time, or the one that will be
+  /// free the soonest if they all are
+  fn pick(&mut self, busy: &HashMap<WebhookId, SystemTime>) -> Option<channel_webhook::Model> {
+    let len = self.webhooks.len();
+    let index = (0..len)
+      .map(|i| (self.next + i) % len)
+      .find(|i| !busy.contains_key(&WebhookId(self.webhooks[*i].id)))
+      .or_else(|| (0..len).min_by_key(|i| busy.get(&WebhookId(self.webhooks[*i].id)).copied()))?;
+    self.next = index + 1;
+    Some(self.webhooks[index].clone())
+  }
+
+  pub fn ids(&self) -> impl Iterator<Item = WebhookId> + '_ {
+    self.webhooks.iter().map(|wh| WebhookId(wh.id))
+  }
+}
+
+/// How many webhooks to post through in each channel, as configured by `webhook_pool_size`
+fn pool_size() -> usize {
+  CONFIG
+    .get_int("webhook_pool_size")
+    .map_or(1, |n| n.clamp(1, MAX_CHANNEL_WEBHOOKS as i64) as usize)
+}
+
+/// Whether the bot made this webhook for `chid`. The first one is called
+/// `stickysurgery-{chid}`, any more are numbered like `stickysurgery-{chid}-2`.
+fn is_own_webhook(webhook: &Webhook, chid: ChannelId) -> bool {
+  let base = format!("{WEBHOOK_PREFIX}{chid}");
+  webhook.name.as_ref().is_some_and(|name| {
+    name == &base ||
+      name.strip_prefix(&format!("{base}-")).is_some_and(|n| n.parse::<u32>().is_ok())
+  })
+}
+
+pub async fn webhook_cache(ctx: &Context) -> Arc<RwLock<HashMap<ChannelId, WebhookPool>>> {
+  let data_read = ctx.data.read().await;
+  data_read.get::<WebhookCache>().expect("Expected to find the Webhook Cache").clone()
+}
+
+/// Fills the cache with the webhooks remembered in the database, so that channels
+/// don't need to be searched for them again after every restart
+pub async fn load_pools<CH: CacheHttp>(
+  db: &StickerDatabase<CH>
+) -> Result<HashMap<ChannelId, WebhookPool>> {
+  let mut pools: HashMap<ChannelId, WebhookPool> = HashMap::new();
+  for webhook in db.get_channel_webhooks(None).await? {
+    pools.entry(ChannelId(webhook.channel)).or_default().webhooks.push(webhook);
+  }
+  info!("Loaded the webhooks of {} channels", pools.len());
+  Ok(pools)
+}
+
+/// Picks a webhook to post through in the channel, finding or creating them if needed
+pub async fn pick_webhook(ctx: &Context, chid: ChannelId) -> Result<channel_webhook::Model> {
+  if is_avoiding_webhooks(chid) {
+    return Err(Error::MissingPermissions(Permissions::MANAGE_WEBHOOKS));
+  }
+  let whmap_lock = webhook_cache(ctx).await;
+  let mut ids: Option<Vec<WebhookId>> =
+    whmap_lock.read().await.get(&chid).map(|p| p.ids().collect());
+  if ids.is_none() {
+    // creating the pool takes a bunch of requests, which other channels shouldn't wait for
+    let _creating = POOL_CREATION.lock().await;
+    if !whmap_lock.read().await.contains_key(&chid) {
+      let pool = match create_pool(ctx, chid).await {
+        Err(e) if e.is_webhook_unusable() => {
+          avoid_webhooks(chid);
+          return Err(e);
+        }
+        pool => pool?,
+      };
+      whmap_lock.write().await.insert(chid, pool);
+    }
+    ids = whmap_lock.read().await.get(&chid).map(|p| p.ids().collect());
+  }
+
+  let busy = busy_webhooks(ctx, &ids.unwrap_or_default()).await;
+  let mut whmap = whmap_lock.write().await;
+  // the pool might have been evicted while looking at the rate limits
+  whmap
+    .get_mut(&chid)
+    .and_then(|pool| pool.pick(&busy))
+    .ok_or(Error::Other(format!("No webhook is available for channel {chid}")))
+}
+
+/// Whether webhooks are still left alone in the channel
+fn is_avoiding_webhooks(chid: ChannelId) -> bool {
+  let mut without = WITHOUT_WEBHOOKS.lock().unwrap();
+  without.retain(|_, until| *until > Instant::now());
+  without.contains_key(&chid)
+}
+
+/// Leaves webhooks alone in the channel for a while after the bot turned out to lack the
+/// permissions for them (or the room for one), so that posts there don't each fail at them first
+pub fn avoid_webhooks(chid: ChannelId) {
+  info!("Not using webhooks in channel {chid} for a while, the bot can't get one there");
+  WITHOUT_WEBHOOKS.lock().unwrap().insert(chid, Instant::now() + MISSING_PERMISSIONS_RETRY);
+}
+
+/// Until when each of the webhooks is held up by its rate limit, as far as serenity's
+/// rate limiter knows. Webhooks it's posting through right now count as busy until now,
+/// so that idle ones go first.
+async fn busy_webhooks(ctx: &Context, ids: &[WebhookId]) -> HashMap<WebhookId, SystemTime> {
+  let now = SystemTime::now();
+  let routes = ctx.http.ratelimiter.routes();
+  let routes = routes.read().await;
+  let mut busy = HashMap::new();
+  for id in ids {
+    let bucket = match routes.get(&Route::WebhooksId(id.0)) {
+      Some(bucket) => bucket,
+      None => {
+        continue;
+      }
+    };
+    // serenity holds the lock for as long as a request (or waiting for its turn) takes
+    let until = match bucket.try_lock() {
+      Ok(limit) if limit.remaining() > 0 => None,
+      Ok(limit) => limit.reset().filter(|reset| *reset > now),
+      Err(_) => Some(now),
+    };
+    if let Some(until) = until {
+      busy.insert(*id, until);
+    }
+  }
+  busy
+}
+
+/// Reuses the bot's existing webhooks in the channel, creating more until the pool is
+/// as big as configured or the channel can't have any more webhooks
+async fn create_pool(ctx: &Context, chid: ChannelId) -> Result<WebhookPool> {
+  let all_webhooks = chid
+    .webhooks(&ctx.http).await
+    .map_err(|e| Error::from_serenity_with_permissions(e, Permissions::MANAGE_WEBHOOKS))?;
+  let others = all_webhooks.iter().filter(|wh| !is_own_webhook(wh, chid)).count();
+  let mut webhooks: Vec<Webhook> = all_webhooks
+    .into_iter()
+    .filter(|wh| is_own_webhook(wh, chid) && wh.token.is_some())
+    .collect();
+  if !webhooks.is_empty() {
+    info!("Found {} webhooks for channel {chid}, reusing them", webhooks.len());
+  }
+
+  let wanted = pool_size().min(MAX_CHANNEL_WEBHOOKS.saturating_sub(others));
+  if webhooks.is_empty() && wanted == 0 {
+    return Err(Error::WebhookLimit);
+  }
+  let mut number = 1;
+  while webhooks.len() < wanted {
+    let name = match number {
+      1 => format!("{WEBHOOK_PREFIX}{chid}"),
+      n => format!("{WEBHOOK_PREFIX}{chid}-{n}"),
+    };
+    number += 1;
+    if webhooks.iter().any(|wh| wh.name.as_ref() == Some(&name)) {
+      continue;
+    }
+    info!("Creating webhook {name}");
+    let created = chid
+      .create_webhook(&ctx.http, name).await
+      .map_err(|e| Error::from_serenity_with_permissions(e, Permissions::MANAGE_WEBHOOKS));
+    match created {
+      Ok(webhook) => webhooks.push(webhook),
+      // someone else took the room we counted on, so make do with what we have
+      Err(e) if e.is_webhook_limit() => {
+        if webhooks.is_empty() {
+          return Err(Error::WebhookLimit);
+        }
+        break;
+      }
+      Err(e) => {
+        return Err(e);
+      }
+    }
+  }
+
+  // the cleanup goes by guild, so every webhook needs one
+  let channel_guild = if webhooks.iter().any(|wh| wh.guild_id.is_none()) {
+    Some(channel_guild(ctx, chid).await?)
+  } else {
+    None
+  };
+  let db = sticker_db(ctx).await;
+  let mut pool = WebhookPool::default();
+  for webhook in webhooks {
+    let guild = webhook.guild_id.or(channel_guild).expect("Expected the channel's guild");
+    let webhook = channel_webhook::Model {
+      id: webhook.id.0,
+      channel: chid.0,
+      guild: guild.0,
+      token: webhook.token.unwrap_or_default(),
+      created_at: webhook.id.created_at().to_string(),
+      last_used: None,
+    };
+    db.save_channel_webhook(webhook.clone()).await?;
+    pool.webhooks.push(webhook);
+  }
+  Ok(pool)
+}
+
+/// The guild a channel belongs to, from the cache if possible
+async fn channel_guild(ctx: &Context, chid: ChannelId) -> Result<GuildId> {
+  if let Some(channel) = ctx.cache.guild_channel(chid) {
+    return Ok(channel.guild_id);
+  }
+  match chid.to_channel(ctx).await? {
+    Channel::Guild(channel) => Ok(channel.guild_id),
+    _ => Err(Error::Other(format!("Channel {chid} is not in a guild"))),
+  }
+}
+
+/// Forgets a webhook of the channel, so the next post uses another one or makes a new one
+pub async fn evict_webhook(ctx: &Context, chid: ChannelId, webhook: WebhookId) {
+  {
+    // don't hold up every other channel's posts while the database is busy
+    let whmap_lock = webhook_cache(ctx).await;
+    let mut whmap = whmap_lock.write().await;
+    if let Some(pool) = whmap.get_mut(&chid) {
+      pool.webhooks.retain(|wh| wh.id != webhook.0);
+      if pool.webhooks.is_empty() {
+        whmap.remove(&chid);
+      }
+    }
+  }
+  if let Err(why) = sticker_db(ctx).await.remove_channel_webhook(webhook).await {
+    warn!("Could not forget webhook {webhook}: {why}");
+  }
+}
+
+/// Forgets the webhooks of a deleted channel, which Discord deleted along with it
+pub async fn forget_channel(ctx: &Context, chid: ChannelId) -> Result<()> {
+  webhook_cache(ctx).await.write().await.remove(&chid);
+  let db = sticker_db(ctx).await;
+  for webhook in db.get_webhooks_of_channel(chid).await? {
+    db.remove_channel_webhook(WebhookId(webhook.id)).await?;
+  }
+  Ok(())
+}
+
+/// Deletes and forgets the webhooks of a guild the bot isn't in anymore. Their tokens are
+/// enough to delete them, but they might well be gone already.
+pub async fn forget_guild(ctx: &Context, guild: GuildId) -> Result<usize> {
+  let db = sticker_db(ctx).await;
+  let webhooks = db.get_channel_webhooks(Some(guild)).await?;
+  for webhook in &webhooks {
+    if let Err(why) = ctx.http.delete_webhook_with_token(webhook.id, &webhook.token).await {
+      debug!("Could not delete webhook {} of guild {guild}: {why}", webhook.id);
+    }
+    db.remove_channel_webhook(WebhookId(webhook.id)).await?;
+  }
+  let whmap_lock = webhook_cache(ctx).await;
+  let mut whmap = whmap_lock.write().await;
+  for webhook in &webhooks {
+    whmap.remove(&ChannelId(webhook.channel));
+  }
+  Ok(webhooks.len())
+}
+
+/// How long a webhook may go without posting before the cleanup removes it, as configured
+/// by `webhook_max_idle_days`, or `None` to keep webhooks no matter how long they're idle
+fn max_idle() -> Option<Duration> {
+  match CONFIG.get_int("webhook_max_idle_days").unwrap_or(30) {
+    days if days > 0 => Some(Duration::from_secs(days as u64 * 24 * 60 * 60)),
+    _ => None,
+  }
+}
+
+/// Whether the webhook hasn't posted (or, if it never did, hasn't existed) for too long
+fn is_idle(webhook: &channel_webhook::Model, max_idle: Option<Duration>) -> bool {
+  let max_idle = match max_idle {
+    Some(max_idle) => max_idle.as_secs() as i64,
+    None => {
+      return false;
+    }
+  };
+  let last_used = webhook.last_used.as_ref().unwrap_or(&webhook.created_at);
+  Timestamp::parse(last_used).is_ok_and(|t| {
+    Timestamp::now().unix_timestamp() - t.unix_timestamp() > max_idle
+  })
+}
+
+/// Removes the bot's webhooks in the guild that it doesn't need anymore: ones that haven't
+/// posted in a while, ones no pool knows about, and ones named after another channel than
+/// their own. Also forgets webhooks that are gone already, like those of deleted channels.
+/// Returns how many webhooks were removed.
+pub async fn cleanup_guild(ctx: &Context, guild: GuildId) -> Result<usize> {
+  let existing = guild
+    .webhooks(&ctx.http).await
+    .map_err(|e| Error::from_serenity_with_permissions(e, Permissions::MANAGE_WEBHOOKS))?;
+  let known = sticker_db(ctx).await.get_channel_webhooks(Some(guild)).await?;
+  let bot = ctx.cache.current_user_id();
+  let max_idle = max_idle();
+
+  let mut removed = 0;
+  for webhook in &existing {
+    let own =
+      webhook.user.as_ref().is_some_and(|u| u.id == bot) &&
+      webhook.name.as_ref().is_some_and(|n| n.starts_with(WEBHOOK_PREFIX));
+    let chid = match webhook.channel_id {
+      Some(chid) if own => chid,
+      _ => {
+        continue;
+      }
+    };
+    let unneeded = match known.iter().find(|wh| wh.id == webhook.id.0) {
+      Some(known) => is_idle(known, max_idle),
+      None => true,
+    };
+    if unneeded || !is_own_webhook(webhook, chid) {
+      info!("Removing webhook {} of channel {chid}", webhook.id);
+      if let Err(why) = ctx.http.delete_webhook(webhook.id.0).await {
+        warn!("Could not delete webhook {} of channel {chid}: {why}", webhook.id);
+        continue;
+      }
+      evict_webhook(ctx, chid, webhook.id).await;
+      removed += 1;
+    }
+  }
+  for webhook in known.iter().filter(|wh| !existing.iter().any(|e| e.id.0 == wh.id)) {
+    info!("Webhook {} of channel {} is gone", webhook.id, webhook.channel);
+    evict_webhook(ctx, ChannelId(webhook.channel), WebhookId(webhook.id)).await;
+    removed += 1;
+  }
+  Ok(removed)
+}
+
+/// Cleans up the webhooks of every guild, including those of guilds the bot has left
+/// while it was offline. Guilds where it may not manage webhooks are skipped.
+async fn cleanup_all(ctx: &Context) -> Result<usize> {
+  let guilds = ctx.cache.guilds();
+  let mut left: Vec<GuildId> = sticker_db(ctx).await
+    .get_channel_webhooks(None).await?
+    .iter()
+    .map(|wh| GuildId(wh.guild))
+    // webhooks used to be saved with guild 0 when Discord didn't say, which isn't a left guild
+    .filter(|guild| guild.0 != 0 && !guilds.contains(guild))
+    .collect();
+  left.sort();
+  left.dedup();
+
+  let mut removed = 0;
+  for guild in left {
+    info!("Forgetting the webhooks of guild {guild}, which the bot has left");
+    removed += forget_guild(ctx, guild).await?;
+  }
+  for guild in guilds {
+    match cleanup_guild(ctx, guild).await {
+      Ok(n) => {
+        removed += n;
+      }
+      Err(Error::MissingPermissions(_)) => debug!("May not manage webhooks in guild {guild}"),
+      Err(why) => warn!("Could not clean up the webhooks of guild {guild}: {why}"),
+    }
+  }
+  Ok(removed)
+}
+
+/// Starts cleaning up webhooks every `webhook_cleanup_interval_hours`, unless that's 0.
+/// The first cleanup waits a full interval, so that the cache knows all guilds by then.
+pub fn start_cleanup(ctx: &Context) {
+  let hours = CONFIG.get_int("webhook_cleanup_interval_hours").unwrap_or(24);
+  if hours <= 0 || CLEANUP_STARTED.swap(true, Ordering::SeqCst) {
+    return;
+  }
+  let ctx = ctx.clone();
+  tokio::spawn(async move {
+    let period = Duration::from_secs(hours as u64 * 60 * 60);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+      interval.tick().await;
+      match cleanup_all(&ctx).await {
+        Ok(removed) => info!("Webhook cleanup removed {removed} webhooks"),
+        Err(why) => error!("Error cleaning up webhooks: {:?}", why),
+      }
+    }
+  });
+}
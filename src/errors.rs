@@ -1,26 +1,128 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::io::Error as IoError;
 use std::result::Result as StdResult;
+use std::time::Duration;
+use hyper::Error as HyperError;
 use sea_orm::DbErr;
+use serenity::http::error::{ Error as HttpError, ErrorResponse };
+use serenity::model::{
+    error::Error as ModelError,
+    id::{ ChannelId, GuildId, RoleId, UserId },
+    Permissions,
+};
 use serenity::Error as SerenityError;
 use tracing::instrument;
 
 pub type Result<T> = StdResult<T, Error>;
 
+/// Whose sticker posts a rate limit counts
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum RateLimitScope {
+    User(UserId),
+    Channel(ChannelId),
+    Guild(GuildId),
+}
+
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Error {
-    /// An error from the `serenity` crate
-    Serenity(SerenityError),
+    /// An error from the `serenity` crate, boxed since it's much bigger than all the others
+    Serenity(Box<SerenityError>),
     /// An error from the `sea-orm` crate
     SeaOrm(DbErr),
+    /// An error from the `hyper` crate
+    Hyper(HyperError),
+    /// An error from file system access
+    Io(IoError),
+    /// The bot lacks these permissions for what it tried to do
+    MissingPermissions(Permissions),
+    /// The channel already has as many webhooks as Discord allows, none of them the bot's
+    WebhookLimit,
+    /// The user can't use any sticker by that reference, though maybe one of the suggestions
+    StickerNotFound { reference: String, suggestions: Vec<String> },
+    /// The user has a role that may not use stickers in this guild
+    BlockedByRole(RoleId),
+    /// The guild only lets some roles use stickers, and the user has none of them
+    NotWhitelisted,
+    /// The sticker is a personal one, which the guild doesn't allow
+    PersonalDisabled(String),
+    /// Too many stickers were posted in this scope lately, try again after the duration
+    RateLimited(RateLimitScope, Duration),
     /// Generic error message
     Other(String),
 }
 
+/// Discord's JSON error code for "Missing Permissions"
+const MISSING_PERMISSIONS_CODE: isize = 50013;
+/// Discord's JSON error code for "Unknown Webhook"
+const UNKNOWN_WEBHOOK_CODE: isize = 10015;
+/// Discord's JSON error code for "Maximum number of webhooks reached"
+const MAX_WEBHOOKS_CODE: isize = 30007;
+
+impl Error {
+    /// Whether Discord says the webhook this was about doesn't exist (anymore)
+    pub fn is_unknown_webhook(&self) -> bool {
+        self.unsuccessful_request().is_some_and(|res| res.error.code == UNKNOWN_WEBHOOK_CODE)
+    }
+
+    /// Whether Discord refused to create a webhook since the channel has too many
+    pub fn is_webhook_limit(&self) -> bool {
+        self.unsuccessful_request().is_some_and(|res| res.error.code == MAX_WEBHOOKS_CODE)
+    }
+
+    /// Whether the bot can't post through webhooks in the channel this was about, and
+    /// should post as itself instead
+    pub fn is_webhook_unusable(&self) -> bool {
+        matches!(self, Error::MissingPermissions(_) | Error::WebhookLimit)
+    }
+
+    /// What Discord answered, if this is about Discord refusing a request
+    fn unsuccessful_request(&self) -> Option<&ErrorResponse> {
+        if let Error::Serenity(e) = self {
+            if let SerenityError::Http(e) = e.as_ref() {
+                if let HttpError::UnsuccessfulRequest(res) = e.as_ref() {
+                    return Some(res);
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether this is about something the user asked for, rather than the bot failing
+    pub fn is_user_error(&self) -> bool {
+        matches!(
+            self,
+            Error::StickerNotFound { .. } |
+                Error::BlockedByRole(_) |
+                Error::NotWhitelisted |
+                Error::PersonalDisabled(_) |
+                Error::RateLimited(..)
+        )
+    }
+
+    /// Like `From<SerenityError>`, but recognizes Discord refusing an action
+    /// for lack of the `required` permissions as `Error::MissingPermissions`
+    pub fn from_serenity_with_permissions(e: SerenityError, required: Permissions) -> Self {
+        match e {
+            SerenityError::Model(ModelError::InvalidPermissions(missing)) => {
+                Error::MissingPermissions(missing)
+            }
+            SerenityError::Http(ref inner) if is_missing_permissions(inner) => {
+                Error::MissingPermissions(required)
+            }
+            e => Error::from(e),
+        }
+    }
+}
+
+fn is_missing_permissions(e: &HttpError) -> bool {
+    matches!(e, HttpError::UnsuccessfulRequest(res) if res.error.code == MISSING_PERMISSIONS_CODE)
+}
+
 impl From<SerenityError> for Error {
   fn from(e: SerenityError) -> Self {
-    Error::Serenity(e)
+    Error::Serenity(Box::new(e))
   }
 }
 
@@ -30,14 +132,53 @@ impl From<DbErr> for Error {
   }
 }
 
+impl From<HyperError> for Error {
+  fn from(e: HyperError) -> Self {
+    Error::Hyper(e)
+  }
+}
+
+impl From<IoError> for Error {
+  fn from(e: IoError) -> Self {
+    Error::Io(e)
+  }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Other(msg) => f.write_str(msg),
+            Self::MissingPermissions(perms) => write!(f, "The bot is missing permissions: {}", perms),
+            Self::WebhookLimit => f.write_str("The channel has no room for another webhook"),
+            Self::StickerNotFound { reference, suggestions } => {
+                let names: Vec<_> = suggestions.iter().map(|s| format!("`:{s}:`")).collect();
+                match names.split_last() {
+                    None => write!(f, "No `:{reference}:` sticker is available"),
+                    Some((last, [])) => write!(f, "No `:{reference}:` \u{2014} did you mean {last}?"),
+                    Some((last, rest)) => {
+                        write!(f, "No `:{reference}:` \u{2014} did you mean {} or {last}?", rest.join(", "))
+                    }
+                }
+            }
+            Self::BlockedByRole(role) => write!(f, "Your role <@&{}> may not use stickers here", role),
+            Self::NotWhitelisted => f.write_str("Only some roles may use stickers here, and you have none of them"),
+            Self::PersonalDisabled(reference) => {
+                write!(f, "`:{reference}:` is a personal sticker, which this server doesn't allow")
+            }
+            Self::RateLimited(scope, wait) => {
+                let who = match scope {
+                    RateLimitScope::User(_) => "You are",
+                    RateLimitScope::Channel(_) => "This channel is",
+                    RateLimitScope::Guild(_) => "This server is",
+                };
+                write!(f, "{who} posting stickers too fast, try again in {} seconds", wait.as_secs() + 1)
+            }
             // Self::ExceededLimit(..) => f.write_str("Input exceeded a limit"),
             // Self::NotInRange(..) => f.write_str("Input is not in the specified range"),
             Self::Serenity(inner) => fmt::Display::fmt(&inner, f),
             Self::SeaOrm(inner) => fmt::Display::fmt(&inner, f),
+            Self::Hyper(inner) => fmt::Display::fmt(&inner, f),
+            Self::Io(inner) => fmt::Display::fmt(&inner, f),
         }
     }
 }
@@ -46,8 +187,10 @@ impl StdError for Error {
     #[instrument]
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
-            Self::Serenity(inner) => Some(inner),
+            Self::Serenity(inner) => Some(inner.as_ref()),
             Self::SeaOrm(inner) => Some(inner),
+            Self::Hyper(inner) => Some(inner),
+            Self::Io(inner) => Some(inner),
             _ => None,
         }
     }
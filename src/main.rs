@@ -1,10 +1,8 @@
-#![feature(let_chains)]
-
 mod errors;
-mod stickers_old;
 mod discord;
 mod db;
 mod stickers;
+mod server;
 
 use std::sync::Arc;
 
@@ -32,6 +30,6 @@ async fn main() -> Result<()> {
   // as far as I can tell, the DatabaseConnection is always used immutably,
   // so I don't actually need an RwLock around it (just an Arc so I can pass it around)
   let db = Arc::new(db::init("sqlite:./main.db").await?);
-  discord::init(db.clone()).await?;
-  Ok(())
+  server::start();
+  discord::init(db).await
 }
\ No newline at end of file
@@ -0,0 +1,168 @@
+use crate::CONFIG;
+use crate::errors::{ Error, Result };
+
+use hyper::{
+  header,
+  service::{ make_service_fn, service_fn },
+  Body,
+  Method,
+  Request,
+  Response,
+  Server,
+  StatusCode,
+};
+use log::{ debug, error, info };
+use percent_encoding::percent_decode_str;
+use std::{ convert::Infallible, net::SocketAddr, path::PathBuf, time::UNIX_EPOCH };
+
+/// How long clients (Discord's media proxy, mostly) may cache a sticker image
+const CACHE_MAX_AGE: u64 = 60 * 60 * 24;
+
+/// Serves selfhosted sticker images from the local `stickers` directory,
+/// using the same layout as `LSticker::image_url`:
+/// `/g/{guild}/{file}`, `/u/{user}/{file}` and `/p/{pack}/{file}`.
+/// Starts the image server in the background, if an `http_address` is configured
+pub fn start() {
+  let addr = match CONFIG.get_string("http_address") {
+    Ok(addr) => addr,
+    Err(_) => {
+      info!("No http_address configured, not serving sticker images");
+      return;
+    }
+  };
+  // the bot doesn't need the image server, so it keeps running if that fails
+  tokio::spawn(async move {
+    if let Err(why) = init(&addr).await {
+      error!("Sticker image server stopped: {why}");
+    }
+  });
+}
+
+async fn init(addr: &str) -> Result<()> {
+  let addr: SocketAddr = addr
+    .parse()
+    .map_err(|e| Error::Other(format!("Invalid http_address: {e}")))?;
+
+  let make_svc = make_service_fn(|_conn| async {
+    Ok::<_, Infallible>(service_fn(serve_sticker))
+  });
+
+  info!("Serving sticker images on http://{addr}");
+  Server::try_bind(&addr)?.serve(make_svc).await?;
+  Ok(())
+}
+
+async fn serve_sticker(req: Request<Body>) -> std::result::Result<Response<Body>, Infallible> {
+  if req.method() != Method::GET && req.method() != Method::HEAD {
+    return Ok(empty_response(StatusCode::METHOD_NOT_ALLOWED));
+  }
+  let path = match sticker_path(req.uri().path()) {
+    Some(path) => path,
+    None => {
+      return Ok(empty_response(StatusCode::NOT_FOUND));
+    }
+  };
+  let metadata = match tokio::fs::metadata(&path).await {
+    Ok(metadata) if metadata.is_file() => metadata,
+    _ => {
+      debug!("Requested sticker image {} does not exist", path.display());
+      return Ok(empty_response(StatusCode::NOT_FOUND));
+    }
+  };
+
+  // the file's size and modification time are good enough to tell revisions apart
+  let modified = metadata
+    .modified()
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_secs())
+    .unwrap_or_default();
+  let etag = format!("\"{:x}-{:x}\"", metadata.len(), modified);
+
+  let builder = Response::builder()
+    .header(header::ETAG, &etag)
+    .header(header::CACHE_CONTROL, format!("public, max-age={CACHE_MAX_AGE}"));
+
+  if
+    req
+      .headers()
+      .get(header::IF_NONE_MATCH)
+      .is_some_and(|v| v.as_bytes() == etag.as_bytes())
+  {
+    return Ok(builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap());
+  }
+
+  let builder = builder
+    .header(header::CONTENT_TYPE, mime_guess::from_path(&path).first_or_octet_stream().as_ref())
+    .header(header::CONTENT_LENGTH, metadata.len());
+  if req.method() == Method::HEAD {
+    return Ok(builder.body(Body::empty()).unwrap());
+  }
+  match tokio::fs::read(&path).await {
+    Ok(data) => Ok(builder.body(Body::from(data)).unwrap()),
+    Err(_) => Ok(empty_response(StatusCode::NOT_FOUND)),
+  }
+}
+
+/// Maps a request path to the sticker image in the local file system,
+/// refusing anything that doesn't fit the expected layout.
+fn sticker_path(uri_path: &str) -> Option<PathBuf> {
+  let mut segments = uri_path.trim_start_matches('/').split('/');
+  let dir = match segments.next()? {
+    "g" => "guild",
+    "u" => "user",
+    "p" => "pack",
+    _ => {
+      return None;
+    }
+  };
+  let owner: u64 = segments.next()?.parse().ok()?;
+  let file = percent_decode_str(segments.next()?).decode_utf8().ok()?;
+  if
+    segments.next().is_some() ||
+    file.is_empty() ||
+    file.starts_with('.') ||
+    file.contains(['/', '\\'])
+  {
+    return None;
+  }
+  Some(PathBuf::from("stickers").join(dir).join(owner.to_string()).join(file.as_ref()))
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+  Response::builder().status(status).body(Body::empty()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::sticker_path;
+  use std::path::PathBuf;
+
+  #[test]
+  fn maps_the_url_layout() {
+    assert_eq!(sticker_path("/g/123/cat.png"), Some(PathBuf::from("stickers/guild/123/cat.png")));
+    assert_eq!(sticker_path("/u/4/a%20b.gif"), Some(PathBuf::from("stickers/user/4/a b.gif")));
+    assert_eq!(sticker_path("/p/5/x.webp"), Some(PathBuf::from("stickers/pack/5/x.webp")));
+  }
+
+  #[test]
+  fn refuses_traversal() {
+    assert_eq!(sticker_path("/g/123/.."), None);
+    assert_eq!(sticker_path("/g/123/../../main.db"), None);
+    assert_eq!(sticker_path("/g/123/%2E%2E"), None);
+    assert_eq!(sticker_path("/g/123/..%2F..%2Fmain.db"), None);
+    assert_eq!(sticker_path("/g/123/a%2Fb.png"), None);
+    assert_eq!(sticker_path("/g/123/a%5Cb.png"), None);
+    assert_eq!(sticker_path("/g/../cat.png"), None);
+  }
+
+  #[test]
+  fn refuses_other_layouts() {
+    assert_eq!(sticker_path("/"), None);
+    assert_eq!(sticker_path("/x/123/cat.png"), None);
+    assert_eq!(sticker_path("/g/123"), None);
+    assert_eq!(sticker_path("/g/123/"), None);
+    assert_eq!(sticker_path("/g/123/cat.png/more"), None);
+    assert_eq!(sticker_path("/g/abc/cat.png"), None);
+  }
+}
@@ -1,22 +1,150 @@
-use std::sync::Arc;
+mod cache;
 
-use sea_orm::{ DatabaseConnection, ColumnTrait, EntityTrait, QueryFilter, * };
+use std::{ cmp::Reverse, sync::Arc };
+
+use lazy_static::lazy_static;
+use log::warn;
+use regex::Regex;
+use sea_orm::{
+  sea_query::{ Expr, SimpleExpr },
+  ActiveModelTrait,
+  ColumnTrait,
+  DatabaseConnection,
+  EntityTrait,
+  ModelTrait,
+  QueryFilter,
+  QueryOrder,
+  Set,
+};
 use serenity::http::CacheHttp;
-use serenity::model::prelude::{ GuildId, UserId };
-use serenity::prelude::RwLock;
+use serenity::model::{ prelude::{ ChannelId, GuildId, RoleId, UserId, WebhookId }, Timestamp };
+use sha2::{ Digest, Sha256 };
+use url::Url;
 
+use crate::CONFIG;
 use crate::db::entities::{ prelude::*, * };
-use crate::errors::Result;
+use crate::errors::{ Error, Result };
+use cache::{ ResolutionCache, StickerIndex };
+
+/// Sticker names may only use the characters that the message syntax understands
+pub fn is_valid_sticker_name(name: &str) -> bool {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^[a-zA-Z0-9\-_+ ]{1,32}$").unwrap();
+  }
+  RE.is_match(name)
+}
+
+/// Pack prefixes can't contain anything that would make `:prefix.name:` ambiguous
+pub fn is_valid_pack_prefix(prefix: &str) -> bool {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^[a-z0-9\-_]{1,16}$").unwrap();
+  }
+  RE.is_match(prefix)
+}
+
+/// Levenshtein distance between two strings, ignoring case
+fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.to_lowercase().chars().collect();
+  let b: Vec<char> = b.to_lowercase().chars().collect();
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.iter().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = diagonal + usize::from(ca != cb);
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+  row[b.len()]
+}
+
+/// What came of looking up a sticker reference for some user
+#[derive(Clone, Debug)]
+pub enum Resolution {
+  Found(LSticker),
+  /// None of the stickers the user can use goes by that name
+  NotFound,
+  /// The user has a role that may not use stickers in this guild
+  BlockedByRole(RoleId),
+  /// The guild only lets some roles use stickers, and the user has none of them
+  NotWhitelisted,
+  /// Only the user's personal stickers have it, and the guild doesn't allow those
+  PersonalDisabled,
+}
+impl Resolution {
+  /// The sticker, or the matching error explaining why there is none
+  pub fn into_result(self, reference: &str) -> Result<LSticker> {
+    match self {
+      Resolution::Found(sticker) => Ok(sticker),
+      Resolution::NotFound => {
+        Err(Error::StickerNotFound { reference: reference.to_string(), suggestions: vec![] })
+      }
+      Resolution::BlockedByRole(role) => Err(Error::BlockedByRole(role)),
+      Resolution::NotWhitelisted => Err(Error::NotWhitelisted),
+      Resolution::PersonalDisabled => Err(Error::PersonalDisabled(reference.to_string())),
+    }
+  }
+}
 
+#[derive(Clone, Debug)]
 pub enum StickerSource {
   Guild(GuildId),
   User(UserId),
   Pack(u64),
 }
+impl StickerSource {
+  /// The directory selfhosted images of this source are stored in
+  pub fn image_dir(&self) -> String {
+    match self {
+      StickerSource::Guild(guild) => format!("stickers/guild/{}", guild),
+      StickerSource::User(user) => format!("stickers/user/{}", user),
+      StickerSource::Pack(pack) => format!("stickers/pack/{}", pack),
+    }
+  }
+
+  /// Restricts a sticker query to the stickers owned by this source
+  fn owner_filter(&self) -> SimpleExpr {
+    match self {
+      StickerSource::Guild(guild) => sticker::Column::Guild.eq(guild.0),
+      StickerSource::User(user) => sticker::Column::User.eq(user.0),
+      StickerSource::Pack(pack) => sticker::Column::Pack.eq(*pack),
+    }
+  }
+}
+
+/// The image of a sticker that is about to be created
+pub enum NewImage {
+  /// Image data that will be selfhosted
+  Upload {
+    data: Vec<u8>,
+    content_type: Option<String>,
+    extension: String,
+  },
+  /// An external image URL
+  External(String),
+}
+#[derive(Clone, Debug)]
+pub enum ImageSource {
+  /// A file in the server's local file system (very unlikely in production)
+  Local(String),
+  /// An external image URL, which we just have to trust will persist
+  External(String),
+  /// A selfhosted image, stored under this file name in the owner's sticker directory
+  Hosted(String),
+}
+
+#[derive(Clone, Debug)]
+#[allow(dead_code)]
 pub struct LSticker {
   pub id: u64,
   pub name: String,
   pub source: StickerSource,
+  pub image: ImageSource,
+  pub content_type: Option<String>,
+  pub size: Option<u64>,
+  pub hash: Option<String>,
+  pub uses: u64,
 }
 impl From<&sticker::Model> for LSticker {
   fn from(value: &sticker::Model) -> Self {
@@ -34,15 +162,59 @@ impl From<&sticker::Model> for LSticker {
           panic!()
         }
       },
+      image: match (value.image_source, value.image_location.clone()) {
+        (sticker::ImageSource::Local, Some(path)) => ImageSource::Local(path),
+        (sticker::ImageSource::External, Some(url)) => ImageSource::External(url),
+        // stickers from before images were tracked follow the old `{name}.png` layout
+        (_, file) => ImageSource::Hosted(file.unwrap_or(format!("{}.png", value.name))),
+      },
+      content_type: value.content_type.clone(),
+      size: value.size,
+      hash: value.hash.clone(),
+      uses: value.uses,
+    }
+  }
+}
+impl LSticker {
+  /// Where the sticker image lives in the local file system, if it's stored locally
+  pub fn image_path(&self) -> Option<String> {
+    match &self.image {
+      ImageSource::Local(path) => Some(path.clone()),
+      ImageSource::External(_) => None,
+      ImageSource::Hosted(file) => Some(format!("{}/{}", self.source.image_dir(), file)),
+    }
+  }
+
+  /// The public URL of the sticker image, if it has one
+  pub fn image_url(&self) -> Option<String> {
+    match &self.image {
+      ImageSource::Local(_) => None,
+      ImageSource::External(url) => Some(url.clone()),
+      ImageSource::Hosted(file) => {
+        let (kind, owner) = match &self.source {
+          StickerSource::Guild(guild) => ("g", guild.0),
+          StickerSource::User(user) => ("u", user.0),
+          StickerSource::Pack(pack) => ("p", *pack),
+        };
+        // let the url crate take care of escaping the file name
+        let mut url = Url::parse(&CONFIG.get_string("public_url").ok()?).ok()?;
+        url.path_segments_mut().ok()?.pop_if_empty().extend([kind, &owner.to_string(), file]);
+        Some(url.into())
+      }
     }
   }
 }
 
-struct StickerDatabase<CH: CacheHttp> {
+pub struct StickerDatabase<CH: CacheHttp> {
   db: Arc<DatabaseConnection>,
   cache_http: Arc<CH>,
+  cache: ResolutionCache,
 }
 impl <CH: CacheHttp> StickerDatabase<CH> {
+  pub fn new(db: Arc<DatabaseConnection>, cache_http: Arc<CH>) -> Self {
+    Self { db, cache_http, cache: ResolutionCache::default() }
+  }
+
   pub async fn get_stickers_for_guild(&self, guild: GuildId) -> Result<Vec<LSticker>> {
     Ok(
       Sticker::find()
@@ -75,114 +247,568 @@ impl <CH: CacheHttp> StickerDatabase<CH> {
     )
   }
 
+  /// The packs a guild uses, in the order it subscribed to them
   pub async fn get_packs_for_guild(&self, guild: GuildId) -> Result<Vec<sticker_pack::Model>> {
     Ok(
       StickerPack::find()
         .inner_join(guild_data::Entity)
         .filter(guild_data::Column::Id.eq(guild.0))
+        .order_by_asc(guild_pack_rel::Column::Position)
+        .order_by_asc(sticker_pack::Column::Id)
         .all(self.db.as_ref()).await?
       // .iter()
       // .map(|st| st.into())
       // .collect()
     )
   }
+  /// The packs a user uses, in the order they subscribed to them
   pub async fn get_packs_for_user(&self, user: UserId) -> Result<Vec<sticker_pack::Model>> {
     Ok(
       StickerPack::find()
         .inner_join(user_data::Entity)
         .filter(user_data::Column::Id.eq(user.0))
+        .order_by_asc(user_pack_rel::Column::Position)
+        .order_by_asc(sticker_pack::Column::Id)
         .all(self.db.as_ref()).await?
       // .iter()
       // .map(|st| st.into())
       // .collect()
     )
   }
+  pub async fn get_packs_by_creator(&self, user: UserId) -> Result<Vec<sticker_pack::Model>> {
+    Ok(
+      StickerPack::find()
+        .filter(sticker_pack::Column::Creator.eq(user.0))
+        .order_by_asc(sticker_pack::Column::Prefix)
+        .all(self.db.as_ref()).await?
+    )
+  }
 
-  pub async fn resolve_sticker(
+  pub async fn find_pack(&self, prefix: &str) -> Result<Option<sticker_pack::Model>> {
+    Ok(
+      StickerPack::find()
+        .filter(sticker_pack::Column::Prefix.eq(prefix))
+        .one(self.db.as_ref()).await?
+    )
+  }
+  /// Like `find_pack`, but a missing pack is an error
+  pub async fn get_pack(&self, prefix: &str) -> Result<sticker_pack::Model> {
+    self
+      .find_pack(prefix).await?
+      .ok_or(Error::Other(format!("There is no sticker pack called `{prefix}`")))
+  }
+
+  pub async fn create_pack(
     &self,
-    sticker: String,
-    uid: UserId,
-    gid: Option<GuildId>
-  ) -> Result<Option<LSticker>> {
-    let guild = if let Some(guild) = gid {
-      GuildData::find_by_id(guild.0).one(self.db.as_ref()).await?
-    } else {
-      None
+    prefix: String,
+    display_name: Option<String>,
+    creator: UserId
+  ) -> Result<sticker_pack::Model> {
+    if !is_valid_pack_prefix(&prefix) {
+      return Err(
+        Error::Other(
+          format!("`{prefix}` is not a valid pack prefix: use up to 16 lowercase letters, digits or `-_`")
+        )
+      );
+    }
+    if self.find_pack(&prefix).await?.is_some() {
+      return Err(Error::Other(format!("The prefix `{prefix}` is already taken")));
+    }
+    Ok(
+      (sticker_pack::ActiveModel {
+        prefix: Set(prefix),
+        display_name: Set(display_name),
+        creator: Set(Some(creator.0)),
+        creation_date: Set(Some(Timestamp::now().to_string())),
+        ..Default::default()
+      }).insert(self.db.as_ref()).await?
+    )
+  }
+
+  /// Deletes a pack along with its stickers and subscriptions
+  pub async fn delete_pack(&self, pack: sticker_pack::Model) -> Result<()> {
+    let dir = StickerSource::Pack(pack.id).image_dir();
+    pack.delete(self.db.as_ref()).await?;
+    self.cache.invalidate_all();
+    if let Err(why) = tokio::fs::remove_dir_all(&dir).await {
+      if why.kind() != std::io::ErrorKind::NotFound {
+        warn!("Could not delete image directory {dir} of removed pack: {why}");
+      }
+    }
+    Ok(())
+  }
+
+  pub async fn subscribe_guild(&self, guild: GuildId, pack: &sticker_pack::Model) -> Result<()> {
+    self.ensure_guild_data(guild).await?;
+    if GuildPackRel::find_by_id((guild.0, pack.id)).one(self.db.as_ref()).await?.is_some() {
+      return Err(Error::Other(format!("This server already uses the pack `{}`", pack.prefix)));
+    }
+    let position = GuildPackRel::find()
+      .filter(guild_pack_rel::Column::GuildId.eq(guild.0))
+      .order_by_desc(guild_pack_rel::Column::Position)
+      .one(self.db.as_ref()).await?
+      .map_or(0, |last| last.position + 1);
+    GuildPackRel::insert(guild_pack_rel::ActiveModel {
+      guild_id: Set(guild.0),
+      pack_id: Set(pack.id),
+      position: Set(position),
+    }).exec(self.db.as_ref()).await?;
+    self.cache.invalidate_guild(guild);
+    Ok(())
+  }
+  pub async fn unsubscribe_guild(&self, guild: GuildId, pack: &sticker_pack::Model) -> Result<()> {
+    let res = GuildPackRel::delete_by_id((guild.0, pack.id)).exec(self.db.as_ref()).await?;
+    if res.rows_affected == 0 {
+      return Err(Error::Other(format!("This server doesn't use the pack `{}`", pack.prefix)));
+    }
+    self.cache.invalidate_guild(guild);
+    Ok(())
+  }
+
+  pub async fn subscribe_user(&self, user: UserId, pack: &sticker_pack::Model) -> Result<()> {
+    self.ensure_user_data(user).await?;
+    if UserPackRel::find_by_id((user.0, pack.id)).one(self.db.as_ref()).await?.is_some() {
+      return Err(Error::Other(format!("You already use the pack `{}`", pack.prefix)));
+    }
+    let position = UserPackRel::find()
+      .filter(user_pack_rel::Column::UserId.eq(user.0))
+      .order_by_desc(user_pack_rel::Column::Position)
+      .one(self.db.as_ref()).await?
+      .map_or(0, |last| last.position + 1);
+    UserPackRel::insert(user_pack_rel::ActiveModel {
+      user_id: Set(user.0),
+      pack_id: Set(pack.id),
+      position: Set(position),
+    }).exec(self.db.as_ref()).await?;
+    self.cache.invalidate_user(user);
+    Ok(())
+  }
+  pub async fn unsubscribe_user(&self, user: UserId, pack: &sticker_pack::Model) -> Result<()> {
+    let res = UserPackRel::delete_by_id((user.0, pack.id)).exec(self.db.as_ref()).await?;
+    if res.rows_affected == 0 {
+      return Err(Error::Other(format!("You don't use the pack `{}`", pack.prefix)));
+    }
+    self.cache.invalidate_user(user);
+    Ok(())
+  }
+
+  pub async fn get_guild_data(&self, guild: GuildId) -> Result<Option<guild_data::Model>> {
+    Ok(GuildData::find_by_id(guild.0).one(self.db.as_ref()).await?)
+  }
+  /// Like `get_guild_data`, but from the cache, for the lookups every sticker post makes
+  pub async fn guild_settings(&self, guild: GuildId) -> Result<Option<guild_data::Model>> {
+    if let Some(gd) = self.cache.guild_data(guild) {
+      return Ok(gd);
+    }
+    let gd = self.get_guild_data(guild).await?;
+    self.cache.insert_guild_data(guild, gd.clone());
+    Ok(gd)
+  }
+  /// Looks up the guild's settings, creating the row with defaults if there is none yet
+  pub async fn ensure_guild_data(&self, guild: GuildId) -> Result<guild_data::Model> {
+    if let Some(gd) = self.get_guild_data(guild).await? {
+      return Ok(gd);
+    }
+    let gd = (guild_data::ActiveModel {
+      id: Set(guild.0),
+      personal_allowed: Set(true),
+      manager_role: Set(None),
+      delete_original: Set(None),
+      user_rate_limit: Set(None),
+      channel_rate_limit: Set(None),
+      guild_rate_limit: Set(None),
+      post_as_bot: Set(None),
+    }).insert(self.db.as_ref()).await?;
+    self.cache.insert_guild_data(guild, Some(gd.clone()));
+    Ok(gd)
+  }
+
+  /// Changes the guild's settings with `change`, creating them first if needed
+  pub async fn update_guild_data(
+    &self,
+    guild: GuildId,
+    change: impl FnOnce(&mut guild_data::ActiveModel)
+  ) -> Result<guild_data::Model> {
+    let mut gd: guild_data::ActiveModel = self.ensure_guild_data(guild).await?.into();
+    change(&mut gd);
+    let gd = gd.update(self.db.as_ref()).await?;
+    self.cache.invalidate_guild(guild);
+    Ok(gd)
+  }
+
+  /// The guild's role rules: whitelisted roles may use stickers, the others may not
+  pub async fn get_role_rules(&self, guild: GuildId) -> Result<Vec<role::Model>> {
+    Ok(Role::find().filter(role::Column::Guild.eq(guild.0)).all(self.db.as_ref()).await?)
+  }
+  /// Whitelists or blacklists a role, replacing whatever rule it had before
+  pub async fn set_role_rule(&self, guild: GuildId, role: RoleId, whitelisted: bool) -> Result<()> {
+    self.ensure_guild_data(guild).await?;
+    let rule = role::ActiveModel {
+      id: Set(role.0),
+      guild: Set(guild.0),
+      whitelisted: Set(whitelisted),
     };
+    if Role::find_by_id(role.0).one(self.db.as_ref()).await?.is_some() {
+      rule.update(self.db.as_ref()).await?;
+    } else {
+      rule.insert(self.db.as_ref()).await?;
+    }
+    self.cache.invalidate_guild(guild);
+    Ok(())
+  }
+  /// Removes the rule for `role`, or all of the guild's rules. Returns how many were removed.
+  pub async fn clear_role_rules(&self, guild: GuildId, role: Option<RoleId>) -> Result<u64> {
+    let mut delete = Role::delete_many().filter(role::Column::Guild.eq(guild.0));
+    if let Some(role) = role {
+      delete = delete.filter(role::Column::Id.eq(role.0));
+    }
+    let removed = delete.exec(self.db.as_ref()).await?.rows_affected;
+    self.cache.invalidate_guild(guild);
+    Ok(removed)
+  }
 
+  /// The webhooks the bot made for posting stickers, in one guild or everywhere
+  pub async fn get_channel_webhooks(
+    &self,
+    guild: Option<GuildId>
+  ) -> Result<Vec<channel_webhook::Model>> {
+    let mut query = ChannelWebhook::find();
     if let Some(guild) = guild {
-      let user = uid.to_user(self.cache_http.as_ref()).await?;
-      for r in 
-        Role::find()
-          .inner_join(guild_data::Entity)
-          .filter(guild_data::Column::Id.eq(gid.unwrap().0))
-          .filter(role::Column::Whitelisted.eq(false))
-          .all(self.db.as_ref()).await? 
-      {
-        if user.has_role(self.cache_http.as_ref(), gid.unwrap(), r.id).await? { return Ok(None); }
+      query = query.filter(channel_webhook::Column::Guild.eq(guild.0));
+    }
+    Ok(query.order_by_asc(channel_webhook::Column::Channel).all(self.db.as_ref()).await?)
+  }
+  /// The webhooks the bot made for posting stickers in one channel
+  pub async fn get_webhooks_of_channel(
+    &self,
+    channel: ChannelId
+  ) -> Result<Vec<channel_webhook::Model>> {
+    Ok(
+      ChannelWebhook::find()
+        .filter(channel_webhook::Column::Channel.eq(channel.0))
+        .all(self.db.as_ref()).await?
+    )
+  }
+  /// Remembers a webhook, unless it's known already
+  pub async fn save_channel_webhook(&self, webhook: channel_webhook::Model) -> Result<()> {
+    if ChannelWebhook::find_by_id(webhook.id).one(self.db.as_ref()).await?.is_none() {
+      channel_webhook::ActiveModel::from(webhook).insert(self.db.as_ref()).await?;
+    }
+    Ok(())
+  }
+  pub async fn remove_channel_webhook(&self, webhook: WebhookId) -> Result<()> {
+    ChannelWebhook::delete_by_id(webhook.0).exec(self.db.as_ref()).await?;
+    Ok(())
+  }
+  /// Notes that the webhook just posted something, so the cleanup knows it's still needed
+  pub async fn touch_channel_webhook(&self, webhook: WebhookId) -> Result<()> {
+    ChannelWebhook::update_many()
+      .col_expr(channel_webhook::Column::LastUsed, Expr::value(Timestamp::now().to_string()))
+      .filter(channel_webhook::Column::Id.eq(webhook.0))
+      .exec(self.db.as_ref()).await?;
+    Ok(())
+  }
+
+  /// Looks up the user's data, creating the row if there is none yet
+  pub async fn ensure_user_data(&self, user: UserId) -> Result<user_data::Model> {
+    if let Some(ud) = UserData::find_by_id(user.0).one(self.db.as_ref()).await? {
+      return Ok(ud);
+    }
+    Ok((user_data::ActiveModel { id: Set(user.0) }).insert(self.db.as_ref()).await?)
+  }
+
+  async fn find_sticker(&self, source: &StickerSource, name: &str) -> Result<Option<sticker::Model>> {
+    Ok(
+      Sticker::find()
+        .filter(source.owner_filter())
+        .filter(sticker::Column::Name.eq(name))
+        .one(self.db.as_ref()).await?
+    )
+  }
+
+  /// Checks that `name` can be used for a new sticker of this source
+  async fn check_new_name(&self, source: &StickerSource, name: &str) -> Result<()> {
+    if !is_valid_sticker_name(name) {
+      return Err(
+        Error::Other(
+          format!("`{name}` is not a valid sticker name: use up to 32 letters, digits, spaces or `-_+`")
+        )
+      );
+    }
+    if self.find_sticker(source, name).await?.is_some() {
+      return Err(Error::Other(format!("A sticker called :{name}: already exists")));
+    }
+    Ok(())
+  }
+
+  pub async fn create_sticker(
+    &self,
+    source: StickerSource,
+    name: String,
+    creator: UserId,
+    image: NewImage
+  ) -> Result<LSticker> {
+    self.check_new_name(&source, &name).await?;
+    match source {
+      StickerSource::Guild(guild) => {
+        self.ensure_guild_data(guild).await?;
       }
-      
-      let whitelist = Role::find()
-          .inner_join(guild_data::Entity)
-          .filter(guild_data::Column::Id.eq(gid.unwrap().0))
-          .filter(role::Column::Whitelisted.eq(true))
-          .all(self.db.as_ref()).await?;
-      if whitelist.len() > 0 {
-        let mut yes = false;
-        for r in whitelist {
-          if user.has_role(self.cache_http.as_ref(), gid.unwrap(), r.id).await? { yes = true; }
-        }
-        if yes == false {
-          return Ok(None);
+      StickerSource::User(user) => {
+        self.ensure_user_data(user).await?;
+      }
+      StickerSource::Pack(_) => (),
+    }
+
+    let mut model = sticker::ActiveModel {
+      name: Set(name),
+      creator: Set(Some(creator.0)),
+      creation_date: Set(Some(Timestamp::now().to_string())),
+      ..Default::default()
+    };
+    match &source {
+      StickerSource::Guild(guild) => model.guild = Set(Some(guild.0)),
+      StickerSource::User(user) => model.user = Set(Some(user.0)),
+      StickerSource::Pack(pack) => model.pack = Set(Some(*pack)),
+    }
+    match image {
+      NewImage::Upload { data, content_type, extension } => {
+        // naming the file after its content means renames never have to touch it
+        let hash = format!("{:x}", Sha256::digest(&data));
+        let file = format!("{hash}.{extension}");
+        let dir = source.image_dir();
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(format!("{dir}/{file}"), &data).await?;
+
+        model.image_source = Set(sticker::ImageSource::Hosted);
+        model.image_location = Set(Some(file));
+        model.content_type = Set(content_type);
+        model.size = Set(Some(data.len() as u64));
+        model.hash = Set(Some(hash));
+      }
+      NewImage::External(url) => {
+        model.image_source = Set(sticker::ImageSource::External);
+        model.image_location = Set(Some(url));
+      }
+    }
+    let st = model.insert(self.db.as_ref()).await?;
+    self.cache.invalidate_source(&source);
+    Ok((&st).into())
+  }
+
+  pub async fn rename_sticker(
+    &self,
+    source: &StickerSource,
+    name: &str,
+    new_name: String
+  ) -> Result<LSticker> {
+    let st = self
+      .find_sticker(source, name).await?
+      .ok_or(Error::Other(format!("There is no sticker called :{name}:")))?;
+    self.check_new_name(source, &new_name).await?;
+
+    // stickers from before images were tracked find their file by name, so pin it down
+    let legacy_file = (st.image_source == sticker::ImageSource::Hosted && st.image_location.is_none())
+      .then(|| format!("{}.png", st.name));
+    let mut model: sticker::ActiveModel = st.into();
+    model.name = Set(new_name);
+    if legacy_file.is_some() {
+      model.image_location = Set(legacy_file);
+    }
+    let st = model.update(self.db.as_ref()).await?;
+    self.cache.invalidate_source(source);
+    Ok((&st).into())
+  }
+
+  pub async fn remove_sticker(&self, source: &StickerSource, name: &str) -> Result<()> {
+    let st = self
+      .find_sticker(source, name).await?
+      .ok_or(Error::Other(format!("There is no sticker called :{name}:")))?;
+    let removed: LSticker = (&st).into();
+    st.delete(self.db.as_ref()).await?;
+    self.cache.invalidate_source(source);
+
+    // identical images share one file, so only delete it once nobody uses it anymore
+    if let ImageSource::Hosted(file) = &removed.image {
+      let still_used = Sticker::find()
+        .filter(source.owner_filter())
+        .filter(sticker::Column::ImageLocation.eq(file.as_str()))
+        .one(self.db.as_ref()).await?
+        .is_some();
+      if let (false, Some(path)) = (still_used, removed.image_path()) {
+        if let Err(why) = tokio::fs::remove_file(&path).await {
+          warn!("Could not delete image {path} of removed sticker :{name}:: {why}");
         }
       }
+    }
+    Ok(())
+  }
+
+  /// Fails with `Error::BlockedByRole` or `Error::NotWhitelisted` if the guild's role rules
+  /// don't let `uid` use stickers at all. The user's `roles` in the guild are looked up
+  /// if the caller doesn't know them.
+  async fn check_roles(&self, uid: UserId, gid: GuildId, roles: Option<&[RoleId]>) -> Result<()> {
+    let rules = match self.cache.role_rules(gid) {
+      Some(rules) => rules,
+      None => {
+        let rules = Arc::new(self.get_role_rules(gid).await?);
+        self.cache.insert_role_rules(gid, rules.clone());
+        rules
+      }
+    };
+    if rules.is_empty() {
+      return Ok(());
+    }
+
+    // the member is usually cached already, so this rarely needs a request
+    let roles = match roles {
+      Some(roles) => roles.to_vec(),
+      None => gid.member(self.cache_http.as_ref(), uid).await?.roles,
+    };
+    if let Some(rule) = rules.iter().find(|r| !r.whitelisted && roles.contains(&RoleId(r.id))) {
+      return Err(Error::BlockedByRole(RoleId(rule.id)));
+    }
+    if
+      rules.iter().any(|r| r.whitelisted) &&
+      !rules.iter().any(|r| r.whitelisted && roles.contains(&RoleId(r.id)))
+    {
+      return Err(Error::NotWhitelisted);
+    }
+    Ok(())
+  }
+
+  /// Everything `uid` can resolve in the guild (or in DMs), ignoring role rules
+  async fn index(&self, uid: UserId, gid: Option<GuildId>) -> Result<Arc<StickerIndex>> {
+    if let Some(index) = self.cache.index(uid, gid) {
+      return Ok(index);
+    }
 
-      if guild.personal_allowed {
-        if let Some(st) = self.resolve_by_user(sticker.clone(), uid).await? {
-          return Ok(Some(st));
+    let guild = match gid {
+      Some(guild) => self.guild_settings(guild).await?,
+      None => None,
+    };
+    let personal_allowed = guild.as_ref().is_none_or(|g| g.personal_allowed);
+    let mut index = StickerIndex::new(personal_allowed);
+    let mut packs = vec![];
+    if personal_allowed {
+      for st in self.get_stickers_for_user(uid).await? {
+        index.add(st, None);
+      }
+      packs.extend(self.get_packs_for_user(uid).await?);
+    }
+    if let Some(gid) = gid {
+      for st in self.get_stickers_for_guild(gid).await? {
+        index.add(st, None);
+      }
+      packs.extend(self.get_packs_for_guild(gid).await?);
+    }
+    for pack in packs {
+      for st in self.get_stickers_for_pack(pack.prefix.clone()).await? {
+        index.add(st, Some(&pack.prefix));
+      }
+    }
+
+    let index = Arc::new(index);
+    self.cache.insert_index(uid, gid, index.clone());
+    Ok(index)
+  }
+
+  /// Finds the sticker a user means by `sticker`, either in a guild or in DMs.
+  ///
+  /// `sticker` is either a plain name or qualified with a pack prefix, as in `prefix.name`.
+  /// Plain names are looked up in the user's personal stickers, then the guild's stickers,
+  /// then the packs the user subscribed to and finally the packs the guild subscribed to,
+  /// both in the order of subscription. The first match wins. Guilds that don't allow
+  /// personal stickers skip the user's stickers and packs entirely.
+  ///
+  /// `roles` are the user's roles in the guild, if the caller has them at hand anyway.
+  pub async fn resolve_sticker(
+    &self,
+    sticker: String,
+    uid: UserId,
+    gid: Option<GuildId>,
+    roles: Option<&[RoleId]>
+  ) -> Result<Resolution> {
+    if let Some(gid) = gid {
+      match self.check_roles(uid, gid, roles).await {
+        Ok(()) => (),
+        Err(Error::BlockedByRole(role)) => {
+          return Ok(Resolution::BlockedByRole(role));
+        }
+        Err(Error::NotWhitelisted) => {
+          return Ok(Resolution::NotWhitelisted);
+        }
+        Err(e) => {
+          return Err(e);
         }
       }
+    }
 
-      Ok(
-        Sticker::find()
-          .filter(sticker::Column::Name.eq(sticker.clone()))
-          .filter(sticker::Column::Guild.eq(gid.unwrap().0))
-          .one(self.db.as_ref()).await?
-          .or(
-            Sticker::find()
-              .inner_join(sticker_pack::Entity)
-              .inner_join(guild_data::Entity)
-              .filter(sticker::Column::Name.eq(sticker.clone()))
-              .filter(guild_data::Column::Id.eq(gid.unwrap().0))
-              .one(self.db.as_ref()).await?
-          )
-          .map(|st| (&st).into())
-      )
-    } else {
-      self.resolve_by_user(sticker, uid).await
+    let index = self.index(uid, gid).await?;
+    if let Some(st) = index.get(&sticker) {
+      return Ok(Resolution::Found(st.clone()));
+    }
+    // tell users when it's only the guild's settings keeping them from their sticker
+    if !index.personal_allowed && self.index(uid, None).await?.get(&sticker).is_some() {
+      return Ok(Resolution::PersonalDisabled);
     }
+    Ok(Resolution::NotFound)
   }
 
-  async fn resolve_by_user(&self, sticker: String, uid: UserId) -> Result<Option<LSticker>> {
-    let user = UserData::find_by_id(uid.0).one(self.db.as_ref()).await?;
-    if user.is_none() {
-      return Ok(None);
+  /// All stickers `uid` can use, along with the reference that resolves to each of them.
+  /// Pack stickers hidden behind a sticker of the same name are referenced as `prefix.name`.
+  pub async fn get_available_stickers(
+    &self,
+    uid: UserId,
+    gid: Option<GuildId>,
+    roles: Option<&[RoleId]>
+  ) -> Result<Vec<(String, LSticker)>> {
+    if let Some(gid) = gid {
+      match self.check_roles(uid, gid, roles).await {
+        Ok(()) => (),
+        Err(Error::BlockedByRole(_) | Error::NotWhitelisted) => {
+          return Ok(vec![]);
+        }
+        Err(e) => {
+          return Err(e);
+        }
+      }
+    }
+    let mut available = self.index(uid, gid).await?.available.clone();
+    for (_, sticker) in &mut available {
+      sticker.uses = self.cache.uses(sticker);
     }
+    Ok(available)
+  }
 
-    Ok(
-      Sticker::find()
-        .filter(sticker::Column::Name.eq(sticker.clone()))
-        .filter(sticker::Column::User.eq(uid.0))
-        .one(self.db.as_ref()).await?
-        .or(
-          Sticker::find()
-            .inner_join(sticker_pack::Entity)
-            .inner_join(user_data::Entity)
-            .filter(sticker::Column::Name.eq(sticker.clone()))
-            .filter(user_data::Column::Id.eq(uid.0))
-            .one(self.db.as_ref()).await?
-        )
-        .map(|st| (&st).into())
-    )
+  /// References to the stickers `uid` can use that look like typos of `reference`, closest first
+  pub async fn suggest_stickers(
+    &self,
+    reference: &str,
+    uid: UserId,
+    gid: Option<GuildId>,
+    roles: Option<&[RoleId]>
+  ) -> Result<Vec<String>> {
+    const MAX_SUGGESTIONS: usize = 3;
+    // allow about one typo every three characters
+    let max_distance = (reference.chars().count() / 3).max(1);
+    let mut suggestions: Vec<(usize, Reverse<u64>, String)> = self
+      .get_available_stickers(uid, gid, roles).await?
+      .into_iter()
+      .map(|(r, st)| (edit_distance(reference, &r), Reverse(st.uses), r))
+      .filter(|(distance, _, _)| *distance <= max_distance)
+      .collect();
+    // among equally close stickers, the popular ones are more likely to be meant
+    suggestions.sort();
+    Ok(suggestions.into_iter().take(MAX_SUGGESTIONS).map(|(_, _, r)| r).collect())
   }
-}
\ No newline at end of file
+
+  /// Counts a use of the sticker, which makes it rank higher in suggestions
+  pub async fn record_use(&self, sticker: &LSticker) -> Result<()> {
+    Sticker::update_many()
+      .col_expr(sticker::Column::Uses, Expr::col(sticker::Column::Uses).add(1))
+      .filter(sticker::Column::Id.eq(sticker.id))
+      .exec(self.db.as_ref()).await?;
+    self.cache.record_use(sticker);
+    Ok(())
+  }
+}
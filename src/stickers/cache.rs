@@ -0,0 +1,289 @@
+use super::{ LSticker, StickerSource };
+use crate::db::entities::{ guild_data, role };
+
+use log::{ debug, info };
+use serenity::model::prelude::{ GuildId, UserId };
+use std::collections::HashMap;
+use std::sync::{ atomic::{ AtomicU64, Ordering }, Arc, Mutex };
+use std::time::Instant;
+
+/// How many lookups to wait between logging the cache statistics
+const STATS_INTERVAL: u64 = 100;
+/// Start forgetting the least recently used indexes once there are this many
+const MAX_INDEXES: usize = 10_000;
+
+/// Everything a user can resolve in some guild (or in DMs), in the order of precedence
+/// explained in `StickerDatabase::resolve_sticker`
+#[derive(Debug, Default)]
+pub struct StickerIndex {
+  /// Whether the guild lets the user use their personal stickers and packs
+  pub personal_allowed: bool,
+  /// The sticker each plain name resolves to
+  names: HashMap<String, LSticker>,
+  /// Every pack sticker, as `prefix.name`
+  qualified: HashMap<String, LSticker>,
+  /// All stickers along with the shortest reference that resolves to them
+  pub available: Vec<(String, LSticker)>,
+}
+impl StickerIndex {
+  pub fn new(personal_allowed: bool) -> Self {
+    Self { personal_allowed, ..Default::default() }
+  }
+
+  /// Adds a sticker with less precedence than all stickers added before,
+  /// passing the `prefix` of its pack if it is in one
+  pub fn add(&mut self, sticker: LSticker, prefix: Option<&str>) {
+    if let Some(prefix) = prefix {
+      let qualified = format!("{prefix}.{}", sticker.name);
+      if self.qualified.contains_key(&qualified) {
+        return;
+      }
+      self.qualified.insert(qualified.clone(), sticker.clone());
+      // the name already means another sticker, so this one needs its prefix
+      if self.names.contains_key(&sticker.name) {
+        self.available.push((qualified, sticker));
+        return;
+      }
+    } else if self.names.contains_key(&sticker.name) {
+      return;
+    }
+    self.names.insert(sticker.name.clone(), sticker.clone());
+    self.available.push((sticker.name.clone(), sticker));
+  }
+
+  pub fn get(&self, reference: &str) -> Option<&LSticker> {
+    if reference.contains('.') {
+      self.qualified.get(reference)
+    } else {
+      self.names.get(reference)
+    }
+  }
+}
+
+/// The user and the guild (if not in DMs) an index is about
+type IndexKey = (UserId, Option<GuildId>);
+
+/// Keeps what resolving stickers needs from the database in memory, so that messages
+/// full of stickers don't each cost a bunch of queries. Everything that changes stickers,
+/// packs, subscriptions or guild settings has to invalidate the affected entries.
+#[derive(Default)]
+pub struct ResolutionCache {
+  /// Each index along with when it was last looked up
+  indexes: Mutex<HashMap<IndexKey, (Instant, Arc<StickerIndex>)>>,
+  /// The current use count of every indexed sticker, which changes too often to rebuild
+  /// indexes for
+  uses: Mutex<HashMap<u64, u64>>,
+  role_rules: Mutex<HashMap<GuildId, Arc<Vec<role::Model>>>>,
+  /// The settings of each guild, or `None` if it never changed any
+  guild_data: Mutex<HashMap<GuildId, Option<guild_data::Model>>>,
+  hits: AtomicU64,
+  misses: AtomicU64,
+}
+impl ResolutionCache {
+  pub fn index(&self, user: UserId, guild: Option<GuildId>) -> Option<Arc<StickerIndex>> {
+    let index = self.indexes.lock().unwrap().get_mut(&(user, guild)).map(|(used, index)| {
+      *used = Instant::now();
+      index.clone()
+    });
+    self.count(index.is_some(), || format!("stickers of user {user} in {guild:?}"));
+    index
+  }
+  pub fn insert_index(&self, user: UserId, guild: Option<GuildId>, index: Arc<StickerIndex>) {
+    let mut indexes = self.indexes.lock().unwrap();
+    if indexes.len() >= MAX_INDEXES && !indexes.contains_key(&(user, guild)) {
+      let oldest = indexes.iter().min_by_key(|(_, (used, _))| *used).map(|(key, _)| *key);
+      if let Some(oldest) = oldest {
+        indexes.remove(&oldest);
+      }
+    }
+    let mut uses = self.uses.lock().unwrap();
+    for (_, sticker) in &index.available {
+      // an index built while a use was recorded might have missed it
+      let count = uses.entry(sticker.id).or_default();
+      *count = (*count).max(sticker.uses);
+    }
+    indexes.insert((user, guild), (Instant::now(), index));
+  }
+
+  /// How often the sticker has been used, as far as we know
+  pub fn uses(&self, sticker: &LSticker) -> u64 {
+    let uses = self.uses.lock().unwrap().get(&sticker.id).copied();
+    uses.map_or(sticker.uses, |uses| uses.max(sticker.uses))
+  }
+  pub fn record_use(&self, sticker: &LSticker) {
+    let mut uses = self.uses.lock().unwrap();
+    let count = uses.entry(sticker.id).or_insert(sticker.uses);
+    *count += 1;
+  }
+
+  pub fn role_rules(&self, guild: GuildId) -> Option<Arc<Vec<role::Model>>> {
+    let rules = self.role_rules.lock().unwrap().get(&guild).cloned();
+    self.count(rules.is_some(), || format!("role rules of guild {guild}"));
+    rules
+  }
+  pub fn insert_role_rules(&self, guild: GuildId, rules: Arc<Vec<role::Model>>) {
+    self.role_rules.lock().unwrap().insert(guild, rules);
+  }
+
+  pub fn guild_data(&self, guild: GuildId) -> Option<Option<guild_data::Model>> {
+    let gd = self.guild_data.lock().unwrap().get(&guild).cloned();
+    self.count(gd.is_some(), || format!("settings of guild {guild}"));
+    gd
+  }
+  pub fn insert_guild_data(&self, guild: GuildId, gd: Option<guild_data::Model>) {
+    self.guild_data.lock().unwrap().insert(guild, gd);
+  }
+
+  /// Forgets everything that depends on the guild's stickers, packs or settings
+  pub fn invalidate_guild(&self, guild: GuildId) {
+    self.indexes.lock().unwrap().retain(|(_, g), _| *g != Some(guild));
+    self.role_rules.lock().unwrap().remove(&guild);
+    self.guild_data.lock().unwrap().remove(&guild);
+  }
+  /// Forgets everything that depends on the user's stickers or packs
+  pub fn invalidate_user(&self, user: UserId) {
+    self.indexes.lock().unwrap().retain(|(u, _), _| *u != user);
+  }
+  /// Forgets all indexes, which is what changes to packs need, as anyone might use them
+  pub fn invalidate_all(&self) {
+    self.indexes.lock().unwrap().clear();
+  }
+  pub fn invalidate_source(&self, source: &StickerSource) {
+    match source {
+      StickerSource::Guild(guild) => self.invalidate_guild(*guild),
+      StickerSource::User(user) => self.invalidate_user(*user),
+      StickerSource::Pack(_) => self.invalidate_all(),
+    }
+  }
+
+  fn count(&self, hit: bool, what: impl FnOnce() -> String) {
+    let (hits, misses) = if hit {
+      (self.hits.fetch_add(1, Ordering::Relaxed) + 1, self.misses.load(Ordering::Relaxed))
+    } else {
+      debug!("Sticker cache miss for {}", what());
+      (self.hits.load(Ordering::Relaxed), self.misses.fetch_add(1, Ordering::Relaxed) + 1)
+    };
+    if (hits + misses) % STATS_INTERVAL == 0 {
+      info!("Sticker cache: {hits} hits, {misses} misses");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ ResolutionCache, StickerIndex };
+  use crate::stickers::{ ImageSource, LSticker, StickerSource };
+  use serenity::model::prelude::{ GuildId, UserId };
+
+  fn sticker(id: u64, name: &str, source: StickerSource) -> LSticker {
+    LSticker {
+      id,
+      name: name.to_string(),
+      source,
+      image: ImageSource::Hosted(format!("{name}.png")),
+      content_type: None,
+      size: None,
+      hash: None,
+      uses: 0,
+    }
+  }
+
+  fn resolve(index: &StickerIndex, reference: &str) -> Option<u64> {
+    index.get(reference).map(|sticker| sticker.id)
+  }
+
+  fn references(index: &StickerIndex) -> Vec<(&str, u64)> {
+    index.available.iter().map(|(reference, sticker)| (reference.as_str(), sticker.id)).collect()
+  }
+
+  /// Adds stickers the way `StickerDatabase::index` does: personal, guild, then packs
+  fn example_index() -> StickerIndex {
+    let mut index = StickerIndex::new(true);
+    index.add(sticker(1, "cat", StickerSource::User(UserId(10))), None);
+    index.add(sticker(2, "cat", StickerSource::Guild(GuildId(20))), None);
+    index.add(sticker(3, "dog", StickerSource::Guild(GuildId(20))), None);
+    index.add(sticker(4, "cat", StickerSource::Pack(30)), Some("first"));
+    index.add(sticker(5, "dog", StickerSource::Pack(30)), Some("first"));
+    index.add(sticker(6, "fox", StickerSource::Pack(30)), Some("first"));
+    index.add(sticker(7, "fox", StickerSource::Pack(31)), Some("second"));
+    index.add(sticker(8, "owl", StickerSource::Pack(31)), Some("second"));
+    index
+  }
+
+  #[test]
+  fn plain_names_follow_precedence() {
+    let index = example_index();
+    // personal beats guild beats packs
+    assert_eq!(resolve(&index, "cat"), Some(1));
+    assert_eq!(resolve(&index, "dog"), Some(3));
+    // earlier subscriptions beat later ones
+    assert_eq!(resolve(&index, "fox"), Some(6));
+    assert_eq!(resolve(&index, "owl"), Some(8));
+    assert_eq!(resolve(&index, "bat"), None);
+  }
+
+  #[test]
+  fn qualified_names_reach_shadowed_pack_stickers() {
+    let index = example_index();
+    assert_eq!(resolve(&index, "first.cat"), Some(4));
+    assert_eq!(resolve(&index, "first.dog"), Some(5));
+    assert_eq!(resolve(&index, "first.fox"), Some(6));
+    assert_eq!(resolve(&index, "second.fox"), Some(7));
+    assert_eq!(resolve(&index, "second.owl"), Some(8));
+    // stickers outside of packs have no qualified form, and prefixes have to match
+    assert_eq!(resolve(&index, "second.cat"), None);
+    assert_eq!(resolve(&index, "third.owl"), None);
+    assert_eq!(resolve(&index, ".cat"), None);
+  }
+
+  #[test]
+  fn lists_the_shortest_working_reference() {
+    let index = example_index();
+    assert_eq!(
+      references(&index),
+      vec![
+        ("cat", 1),
+        ("dog", 3),
+        ("first.cat", 4),
+        ("first.dog", 5),
+        ("fox", 6),
+        ("second.fox", 7),
+        ("owl", 8)
+      ]
+    );
+  }
+
+  #[test]
+  fn ignores_colliding_pack_stickers() {
+    let mut index = StickerIndex::new(true);
+    // a pack both the user and the guild subscribed to is added twice
+    index.add(sticker(1, "cat", StickerSource::Pack(30)), Some("pack"));
+    index.add(sticker(1, "cat", StickerSource::Pack(30)), Some("pack"));
+    // as is a different pack that happens to use the same prefix
+    index.add(sticker(2, "cat", StickerSource::Pack(31)), Some("pack"));
+    assert_eq!(resolve(&index, "cat"), Some(1));
+    assert_eq!(resolve(&index, "pack.cat"), Some(1));
+    assert_eq!(references(&index), vec![("cat", 1)]);
+  }
+
+  #[test]
+  fn counts_uses_across_cached_indexes() {
+    let cache = ResolutionCache::default();
+    let cat = sticker(1, "cat", StickerSource::Pack(30));
+    for user in [UserId(10), UserId(11)] {
+      let mut index = StickerIndex::new(true);
+      index.add(cat.clone(), Some("pack"));
+      cache.insert_index(user, None, index.into());
+    }
+    cache.record_use(&cat);
+    cache.record_use(&cat);
+    let cached = cache.index(UserId(11), None).unwrap();
+    assert_eq!(cache.uses(cached.get("cat").unwrap()), 2);
+
+    // rebuilding an index from the database doesn't lose uses recorded in the meantime
+    let mut index = StickerIndex::new(true);
+    index.add(LSticker { uses: 1, ..cat.clone() }, Some("pack"));
+    cache.insert_index(UserId(12), None, index.into());
+    assert_eq!(cache.uses(&cat), 2);
+  }
+}